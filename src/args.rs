@@ -1,10 +1,99 @@
 use crate::passes::PassKind;
+use crate::report::RunInfo;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
 
+// Command line interface of the adaptor. By default, the adaptor generates test-suites
+// using the arguments in `Args`. Other modes of operation are available as subcommands.
+// This is not a doc comment, as clap would print it in `--help`
 #[derive(Parser)]
+#[command(
+    about = "Generate test-suites for gccrs from the rustc and gccrs test-suites, and run them",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+    #[command(flatten)]
+    pub(crate) args: Option<Args>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run an existing test-suite file, without generating it again
+    Run {
+        #[arg(short, long, help = "YAML ftf file to run")]
+        yaml: PathBuf,
+        #[arg(short, long, help = "amount of threads to use", default_value = "1")]
+        jobs: usize,
+        #[arg(
+            long,
+            help = "JSON file in which to write the results of the test-suite"
+        )]
+        report: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "JUnit XML file in which to write the results of the test-suite"
+        )]
+        junit: Option<PathBuf>,
+        #[arg(short, long, help = "rustc compiler to mention in the report")]
+        rustc: Option<PathBuf>,
+        #[arg(short, long, help = "gccrs compiler to mention in the report")]
+        gccrs: Option<PathBuf>,
+        #[arg(
+            long,
+            requires = "report",
+            help = "name of the test run to mention in the report, such as the pass ran"
+        )]
+        report_name: Option<String>,
+        #[arg(
+            long,
+            requires = "report",
+            help = "commit of gccrs which was tested, to mention in the report"
+        )]
+        report_commit: Option<String>,
+        #[arg(
+            long,
+            requires = "report",
+            help = "date of the test run to mention in the report, such as `2023-09-01`"
+        )]
+        report_date: Option<String>,
+    },
+    /// Compare two JSON reports of the same pass and list the test cases which changed
+    /// status. Exits with an error if any test case regressed
+    Compare {
+        #[arg(help = "JSON report of the reference test run")]
+        old: PathBuf,
+        #[arg(help = "JSON report of the test run to check")]
+        new: PathBuf,
+    },
+    /// Convert the rustc test cases which passed in a JSON report into DejaGnu test cases,
+    /// skipping the ones already in the gccrs test-suite
+    Export {
+        #[arg(help = "JSON report of a test run of the rustc passes")]
+        report: PathBuf,
+        #[arg(
+            long,
+            help = "path to the cloned rust repository the test cases come from"
+        )]
+        rust_path: PathBuf,
+        #[arg(long, help = "path to a cloned gccrs repository")]
+        gccrs_path: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "staging directory which will contain the converted test cases"
+        )]
+        output_dir: PathBuf,
+    },
+}
+
+#[derive(Clone, clap::Args)]
 pub struct Args {
     #[arg(
         short,
@@ -22,8 +111,133 @@ pub struct Args {
     pub(crate) rust_path: PathBuf,
     #[arg(long, help = "path to a cloned gccrs repository")]
     pub(crate) gccrs_path: PathBuf,
-    #[arg(short, long, help = "pass to to run in the adaptor")]
-    pub(crate) pass: PassKind,
+    #[arg(
+        short,
+        long,
+        required = true,
+        num_args = 1..,
+        value_delimiter = ',',
+        help = "passes to run in the adaptor, or `all`"
+    )]
+    pub(crate) pass: Vec<PassKind>,
     #[arg(short, long, help = "amount of threads to use", default_value = "1")]
     pub(crate) jobs: usize,
+    #[arg(
+        long,
+        help = "run the generated test cases directly instead of only writing the YAML file"
+    )]
+    pub(crate) run: bool,
+    #[arg(
+        long,
+        requires = "run",
+        help = "JSON file in which to write the results of all the passes ran"
+    )]
+    pub(crate) report: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "run",
+        help = "JUnit XML file in which to write the results of all the passes ran"
+    )]
+    pub(crate) junit: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "report",
+        help = "name of the test run to mention in the report, such as the pass ran"
+    )]
+    pub(crate) report_name: Option<String>,
+    #[arg(
+        long,
+        requires = "report",
+        help = "commit of gccrs which was tested, to mention in the report"
+    )]
+    pub(crate) report_commit: Option<String>,
+    #[arg(
+        long,
+        requires = "report",
+        help = "date of the test run to mention in the report, such as `2023-09-01`"
+    )]
+    pub(crate) report_date: Option<String>,
+    #[arg(
+        long,
+        help = "file in which to cache rustc results between invocations of the adaptor"
+    )]
+    pub(crate) oracle_cache: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "directory containing the lists of expected failures of each pass, named `<pass>.xfail`"
+    )]
+    pub(crate) xfail_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "only keep test files whose path, relative to their repository, matches one of these globs"
+    )]
+    pub(crate) include: Vec<Pattern>,
+    #[arg(
+        long,
+        help = "ignore test files whose path, relative to their repository, matches one of these globs"
+    )]
+    pub(crate) exclude: Vec<Pattern>,
+    #[arg(
+        long,
+        help = "only keep test files whose path, relative to their repository, matches this regex"
+    )]
+    pub(crate) filter: Option<Regex>,
+    #[arg(
+        long,
+        help = "also compare the standard error of prettified binaries in the `ast-export` pass, ignoring source locations"
+    )]
+    pub(crate) compare_stderr: bool,
+}
+
+impl Args {
+    /// Information about the test run to mention in its report
+    pub fn run_info(&self) -> RunInfo {
+        RunInfo {
+            name: self.report_name.clone(),
+            commit: self.report_commit.clone(),
+            date: self.report_date.clone(),
+        }
+    }
+
+    /// Check if a test file should be part of the test-suites, according to the `--include`,
+    /// `--exclude` and `--filter` arguments. `path` is relative to the root of the
+    /// repository containing the test file
+    pub fn is_selected(&self, path: &Path) -> bool {
+        // `*` only matches within a path component, while `**` matches across components
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
+        let matches = |pattern: &Pattern| pattern.matches_path_with(path, options);
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(&path.to_string_lossy()))
+    }
+
+    /// Create the arguments to use when running a specific pass. When running multiple
+    /// passes, each of them gets its own output directory and YAML file, named after the pass
+    pub fn for_pass(&self, pass: PassKind, multiple_passes: bool) -> Args {
+        let mut args = self.clone();
+
+        if multiple_passes {
+            let stem = self
+                .yaml
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let yaml = match self.yaml.extension() {
+                Some(ext) => format!("{stem}-{pass}.{}", ext.to_string_lossy()),
+                None => format!("{stem}-{pass}"),
+            };
+
+            args.output_dir = self.output_dir.join(pass.to_string());
+            args.yaml = self.yaml.with_file_name(yaml);
+        }
+
+        args
+    }
 }
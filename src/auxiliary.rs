@@ -0,0 +1,372 @@
+//! Auxiliary crates of the rustc test-suite. Test cases declare the crates they depend on
+//! with `// aux-build:foo.rs`, which refers to `auxiliary/foo.rs` next to the test case.
+//! These crates need to be compiled as libraries before the test case itself, with the same
+//! compiler, and are not test cases on their own.
+//!
+//! The `link` test cases of the gccrs test-suite work the same way: they depend on other
+//! files of the `link` directory, declared with `dg-additional-sources` or with an
+//! `extern crate` referring to a file of the same name. Like in `link.exp`, the crate root
+//! of a test case is named `foo_0.rs`, and also depends on `foo_1.rs`, `foo_2.rs`, ...
+
+use std::collections::HashSet;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::args::Args;
+use crate::compiler::{Compiler, CrateType, Edition, Kind};
+use crate::compiletest::Headers;
+use crate::dejagnu::{Action, Spec};
+use crate::error::Error;
+use crate::runner;
+
+/// Name of the directories containing auxiliary crates in the rustc test-suite
+const AUXILIARY_DIR: &str = "auxiliary";
+
+/// Name of the directory containing multi-crate test cases in the gccrs test-suite
+const LINK_DIR: &str = "link";
+
+/// Crate type declared by a crate, such as `#![crate_type = "proc-macro"]`
+const CRATE_TYPE: &str = r#"#!\[\s*crate_type\s*=\s*"([^"]+)"\s*\]"#;
+
+/// External crate declaration, such as `extern crate foo;`
+const EXTERN_CRATE: &str = r"(?m)^\s*extern\s+crate\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:as\s+\w+\s*)?;";
+
+/// Maximum amount of time given to a compiler to build a single auxiliary crate
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Is the file an auxiliary crate rather than a test case?
+pub fn is_auxiliary(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str() == AUXILIARY_DIR)
+}
+
+/// Is the file part of a multi-crate test case of the gccrs test-suite, which lives in the
+/// test-suite's `link` directory?
+pub fn is_link_test(path: &Path) -> bool {
+    let mut dirs = path.ancestors().skip(1).map(Path::file_name);
+
+    dirs.next() == Some(Some(LINK_DIR.as_ref())) && dirs.next() == Some(Some("rust".as_ref()))
+}
+
+/// Files numbered after the crate root `foo_0.rs` of a `link` test case
+fn numbered_dependencies(path: &Path) -> Vec<PathBuf> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let Some(prefix) = stem.strip_suffix("_0") else {
+        return Vec::new();
+    };
+
+    (1..)
+        .map(|idx| path.with_file_name(format!("{prefix}_{idx}.rs")))
+        .take_while(|dependency| dependency.is_file())
+        .collect()
+}
+
+/// Files of the crates a `link` test case depends on, declared in its DejaGnu directives,
+/// through `extern crate` declarations referring to a file next to the test case, or
+/// through the numbering of its files
+fn link_dependencies_of(path: &Path, content: &str) -> Vec<PathBuf> {
+    let extern_crate = Regex::new(EXTERN_CRATE).expect("invalid extern crate regex");
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let sources = Spec::parse(content, Action::Link).additional_sources;
+    let extern_crates = extern_crate
+        .captures_iter(content)
+        .map(|captures| dir.join(format!("{}.rs", &captures[1])))
+        .filter(|dependency| dependency.is_file());
+
+    let mut dependencies: Vec<PathBuf> = Vec::new();
+    for dependency in sources
+        .iter()
+        .map(|source| dir.join(source))
+        .chain(extern_crates)
+        .chain(numbered_dependencies(path))
+    {
+        if dependency != path && !dependencies.contains(&dependency) {
+            dependencies.push(dependency);
+        }
+    }
+
+    dependencies
+}
+
+/// Find all the files among `files` which are dependencies of a `link` test case. They
+/// are only compiled as part of the test cases depending on them
+pub fn link_dependencies(files: &[PathBuf]) -> HashSet<&Path> {
+    let dependencies: HashSet<PathBuf> = files
+        .par_iter()
+        .filter(|file| is_link_test(file))
+        .flat_map_iter(|file| {
+            let content = fs::read(file)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default();
+
+            link_dependencies_of(file, &content)
+        })
+        .collect();
+
+    files
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|file| dependencies.contains(*file))
+        .collect()
+}
+
+/// Crate which must be built before a test case
+pub struct AuxCrate {
+    pub name: String,
+    pub path: PathBuf,
+    crate_type: CrateType,
+    edition: Option<Edition>,
+    /// Extra flags to give to `rustc` when building the crate
+    compile_flags: Vec<String>,
+    /// Files of the crates this crate depends on
+    dependencies: Vec<PathBuf>,
+}
+
+impl AuxCrate {
+    /// Read the file of a crate, which might not exist. Missing crates are reported when
+    /// trying to build them
+    fn read(path: &Path) -> String {
+        fs::read(path)
+            .map(|content| String::from_utf8_lossy(&content).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Like `compiletest`, name the crate after its file
+    fn name(path: &Path) -> String {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .replace('-', "_")
+    }
+
+    /// Type of a crate, from its `crate_type` attribute. Crates are built as `rlib`s unless
+    /// they are procedural macros, which makes `// no-prefer-dynamic` irrelevant. Crates
+    /// are always built for the host, like with `// force-host`
+    fn crate_type(content: &str) -> CrateType {
+        let crate_type = Regex::new(CRATE_TYPE).expect("invalid crate type regex");
+
+        match crate_type.captures(content) {
+            Some(captures) if &captures[1] == "proc-macro" => CrateType::ProcMacro,
+            _ => CrateType::Library,
+        }
+    }
+
+    /// Auxiliary crate of the rustc test-suite, whose dependencies are in the same
+    /// `auxiliary` directory
+    fn compiletest(path: PathBuf) -> AuxCrate {
+        let content = AuxCrate::read(&path);
+        let headers = Headers::parse(&content, None);
+        let aux_dir = path.parent().unwrap_or(Path::new("")).to_owned();
+
+        AuxCrate {
+            name: AuxCrate::name(&path),
+            crate_type: AuxCrate::crate_type(&content),
+            edition: headers.edition,
+            compile_flags: headers.compile_flags,
+            dependencies: headers
+                .aux_builds
+                .iter()
+                .map(|aux_build| aux_dir.join(aux_build))
+                .collect(),
+            path,
+        }
+    }
+
+    /// Crate of a `link` test case of the gccrs test-suite. Its options are meant for
+    /// `gccrs`, so only the edition is kept
+    fn dejagnu(path: PathBuf) -> AuxCrate {
+        let content = AuxCrate::read(&path);
+
+        AuxCrate {
+            name: AuxCrate::name(&path),
+            crate_type: CrateType::Library,
+            edition: Spec::parse(&content, Action::Link).edition(),
+            compile_flags: Vec::new(),
+            dependencies: link_dependencies_of(&path, &content),
+            path,
+        }
+    }
+
+    /// Name of the library produced when building the crate with a specific compiler
+    fn library_name(&self, kind: Kind) -> String {
+        match kind {
+            Kind::Rust1 => format!("lib{}.o", self.name),
+            Kind::RustcBootstrap => match self.crate_type {
+                CrateType::Library => format!("lib{}.rlib", self.name),
+                CrateType::ProcMacro => format!("{DLL_PREFIX}{}{DLL_SUFFIX}", self.name),
+            },
+        }
+    }
+}
+
+/// All the auxiliary crates needed by a test case, in the order in which they need to be
+/// built. Each test case gets its own output directory, so that test cases sharing an
+/// auxiliary crate can be adapted in parallel
+pub struct AuxCrates {
+    crates: Vec<AuxCrate>,
+    out_dir: PathBuf,
+}
+
+impl AuxCrates {
+    /// Find the auxiliary crates of a rustc test case, as well as the ones they depend on
+    pub fn resolve(test: &Path, headers: &Headers) -> AuxCrates {
+        let aux_dir = test.with_file_name(AUXILIARY_DIR);
+        let aux_builds = headers
+            .aux_builds
+            .iter()
+            .map(|aux_build| aux_dir.join(aux_build));
+
+        let mut aux_crates = AuxCrates::resolve_with(test, aux_builds, AuxCrate::compiletest);
+
+        for proc_macro in &headers.proc_macros {
+            let path = aux_dir.join(proc_macro);
+            aux_crates
+                .crates
+                .iter_mut()
+                .filter(|aux| aux.path == path)
+                .for_each(|aux| aux.crate_type = CrateType::ProcMacro);
+        }
+
+        aux_crates
+    }
+
+    /// Find the crates a `link` test case of the gccrs test-suite depends on, as well as
+    /// the ones they depend on. Other test cases do not have any
+    pub fn resolve_link(test: &Path, content: &str) -> AuxCrates {
+        let dependencies = if is_link_test(test) {
+            link_dependencies_of(test, content)
+        } else {
+            Vec::new()
+        };
+
+        AuxCrates::resolve_with(test, dependencies, AuxCrate::dejagnu)
+    }
+
+    fn resolve_with(
+        test: &Path,
+        dependencies: impl IntoIterator<Item = PathBuf>,
+        new: fn(PathBuf) -> AuxCrate,
+    ) -> AuxCrates {
+        let mut crates = Vec::new();
+
+        AuxCrates::resolve_all(dependencies, &mut crates, new);
+
+        AuxCrates {
+            crates,
+            out_dir: test.with_extension("aux"),
+        }
+    }
+
+    /// Add the crates in `dependencies` to `crates`, after their own dependencies
+    fn resolve_all(
+        dependencies: impl IntoIterator<Item = PathBuf>,
+        crates: &mut Vec<AuxCrate>,
+        new: fn(PathBuf) -> AuxCrate,
+    ) {
+        for path in dependencies {
+            // Crates may be shared by multiple dependencies, and should only be built once
+            if crates.iter().any(|aux| aux.path == path) {
+                continue;
+            }
+
+            let aux = new(path);
+            let dependencies = aux.dependencies.clone();
+
+            // Push the crate first in order to break dependency cycles, then move it after
+            // its dependencies
+            crates.push(aux);
+            let idx = crates.len() - 1;
+            AuxCrates::resolve_all(dependencies, crates, new);
+            let aux = crates.remove(idx);
+            crates.push(aux);
+        }
+    }
+
+    fn dir(&self, kind: Kind) -> PathBuf {
+        self.out_dir.join(match kind {
+            Kind::Rust1 => "gccrs",
+            Kind::RustcBootstrap => "rustc",
+        })
+    }
+
+    /// Build all the auxiliary crates as libraries. Returns the first crate which the
+    /// compiler did not manage to build, if any
+    pub fn build(&self, kind: Kind, args: &Args) -> Result<Option<&AuxCrate>, Error> {
+        if self.crates.is_empty() {
+            return Ok(None);
+        }
+
+        let dir = self.dir(kind);
+        fs::create_dir_all(&dir)?;
+
+        for (idx, aux) in self.crates.iter().enumerate() {
+            let mut compiler = link(Compiler::new(kind, args), kind, &dir, &self.crates[..idx])
+                .maybe_edition(aux.edition)
+                .crate_name(&aux.name)
+                .crate_type(aux.crate_type)
+                .output(&dir.join(aux.library_name(kind)));
+
+            compiler = match kind {
+                // `gccrs` embeds the crate's metadata in the object file it produces
+                Kind::Rust1 => compiler.flags(&["-c"]),
+                Kind::RustcBootstrap => compiler.flags(&aux.compile_flags),
+            };
+
+            let cmd = compiler.command().arg(&aux.path);
+
+            // The compiler might not exist on this machine, which is reported like a
+            // failed build
+            let built = runner::run_with_timeout(cmd, TIMEOUT)
+                .is_ok_and(|output| output.exit_code == Some(0));
+
+            if !built {
+                return Ok(Some(aux));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// `gccrs` with access to the auxiliary crates, which are built first. Crates which
+    /// `gccrs` fails to build are not reported here: the test case fails when it is run,
+    /// as it cannot find them, which is what we want
+    pub fn gccrs(&self, args: &Args) -> Result<Compiler, Error> {
+        self.build(Kind::Rust1, args)?;
+
+        Ok(self.link(Compiler::new(Kind::Rust1, args), Kind::Rust1))
+    }
+
+    /// Give access to the auxiliary crates built with [`AuxCrates::build`] to a compiler
+    /// invocation. `gccrs` is also given the object files of the crates, so that they are
+    /// linked with the test case
+    pub fn link(&self, compiler: Compiler, kind: Kind) -> Compiler {
+        let dir = self.dir(kind);
+        let compiler = link(compiler, kind, &dir, &self.crates);
+
+        match kind {
+            Kind::Rust1 => self.crates.iter().fold(compiler, |compiler, aux| {
+                compiler.flags(&[dir.join(aux.library_name(kind))])
+            }),
+            Kind::RustcBootstrap => compiler,
+        }
+    }
+}
+
+/// Give access to the auxiliary crates built in `dir` to a compiler invocation
+fn link(compiler: Compiler, kind: Kind, dir: &Path, crates: &[AuxCrate]) -> Compiler {
+    if crates.is_empty() {
+        return compiler;
+    }
+
+    crates
+        .iter()
+        .fold(compiler.library_path(dir), |compiler, aux| {
+            compiler.extern_crate(&aux.name, &dir.join(aux.library_name(kind)))
+        })
+}
@@ -0,0 +1,86 @@
+//! Comparison of two test runs of the same pass, in order to find which test cases changed
+//! status between two versions of `gccrs`.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::report::Report;
+use crate::runner::{Outcome, TestResult};
+
+/// Test cases which changed status between two test runs. Test cases are identified by
+/// their pass and name
+#[derive(Default)]
+pub struct Comparison<'a> {
+    /// Test cases which used to pass, and now fail or time out, expectedly or not
+    pub new_failures: Vec<&'a TestResult>,
+    /// Test cases which used to fail or time out, and now pass, expectedly or not
+    pub new_passes: Vec<&'a TestResult>,
+    /// Test cases which did not use to time out, and now do
+    pub new_timeouts: Vec<&'a TestResult>,
+    /// Test cases which only exist in the new test run
+    pub added: Vec<&'a TestResult>,
+    /// Test cases which only exist in the old test run
+    pub removed: Vec<&'a TestResult>,
+    /// Amount of test cases which used to pass and do not anymore
+    pub regressions: usize,
+}
+
+impl<'a> Comparison<'a> {
+    pub fn new(old: &'a Report, new: &'a Report) -> Comparison<'a> {
+        let id = |result: &'a TestResult| (result.pass.as_str(), result.name.as_str());
+
+        let old_results: HashMap<_, _> = old.tests.iter().map(|r| (id(r), r)).collect();
+        let new_results: HashMap<_, _> = new.tests.iter().map(|r| (id(r), r)).collect();
+
+        let mut comparison =
+            new.tests
+                .iter()
+                .fold(Comparison::default(), |mut comparison, new_result| {
+                    let Some(old_result) = old_results.get(&id(new_result)) else {
+                        comparison.added.push(new_result);
+                        return comparison;
+                    };
+
+                    let (old, new) = (old_result.outcome, new_result.outcome);
+
+                    if old.is_success() && new.is_failure() {
+                        comparison.regressions += 1;
+                        comparison.new_failures.push(new_result);
+                    } else if old.is_failure() && new.is_success() {
+                        comparison.new_passes.push(new_result);
+                    }
+
+                    if old != Outcome::Timeout && new == Outcome::Timeout {
+                        comparison.new_timeouts.push(new_result);
+                    }
+
+                    comparison
+                });
+
+        comparison.removed = old
+            .tests
+            .iter()
+            .filter(|old_result| !new_results.contains_key(&id(old_result)))
+            .collect();
+
+        comparison
+    }
+}
+
+fn write_section(f: &mut Formatter<'_>, title: &str, results: &[&TestResult]) -> FmtResult {
+    writeln!(f, "{title} ({}):", results.len())?;
+
+    results
+        .iter()
+        .try_for_each(|result| writeln!(f, "  - [{}] {}", result.pass, result.name))
+}
+
+impl Display for Comparison<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write_section(f, "new failures", &self.new_failures)?;
+        write_section(f, "new timeouts", &self.new_timeouts)?;
+        write_section(f, "new passes", &self.new_passes)?;
+        write_section(f, "added tests", &self.added)?;
+        write_section(f, "removed tests", &self.removed)
+    }
+}
@@ -2,23 +2,39 @@
 //! with adequate defaults and added functions or types to help make compiler invocations
 //! in the testing project safer, easier and less verbose.
 
-use std::path::Path;
+use std::ffi::OsStr;
+use std::path::{self, Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use crate::args::Args;
 
 /// All *used* Rust editions
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Edition {
+    E2015,
+    E2018,
     E2021,
 }
 
 impl Edition {
-    fn to_str(self) -> &'static str {
+    /// Year of the edition, such as `2018`
+    pub fn to_str(self) -> &'static str {
         match self {
+            Edition::E2015 => "2015",
+            Edition::E2018 => "2018",
             Edition::E2021 => "2021",
         }
     }
+
+    /// Get the edition associated with a year, such as `2018`
+    pub fn from_year(year: &str) -> Option<Edition> {
+        match year.trim() {
+            "2015" => Some(Edition::E2015),
+            "2018" => Some(Edition::E2018),
+            "2021" => Some(Edition::E2021),
+            _ => None,
+        }
+    }
 }
 
 /// All compiler kinds used in the testsuite
@@ -32,6 +48,8 @@ pub enum Kind {
 #[derive(Clone, Copy)]
 pub enum CrateType {
     Library,
+    /// Procedural macro crate, which is loaded by the compiler as a dynamic library
+    ProcMacro,
 }
 
 impl Kind {
@@ -44,6 +62,17 @@ impl Kind {
     }
 }
 
+/// Program to run for a compiler path. Relative paths such as `./gccrs` are made absolute,
+/// so that the compiler can also be run from another working directory. Compilers given
+/// by name are still looked up in the `PATH`
+fn program(path: &Path) -> PathBuf {
+    if path.is_relative() && path.components().count() > 1 {
+        path::absolute(path).unwrap_or_else(|_| path.to_owned())
+    } else {
+        path.to_owned()
+    }
+}
+
 /// Extend the [`Command`] type with functions associated with the compiler we're going to run
 trait CommandExt {
     /// Set the default arguments for a specific compiler
@@ -83,7 +112,7 @@ impl Compiler {
     /// Create a new compiler invocation
     pub fn new(kind: Kind, args: &Args) -> Compiler {
         Compiler {
-            cmd: Command::new(kind.as_path_from_args(args)),
+            cmd: Command::new(program(kind.as_path_from_args(args))),
             kind,
             stdout: None,
             stderr: None,
@@ -112,6 +141,7 @@ impl Compiler {
         if let Kind::RustcBootstrap = self.kind() {
             self.cmd.arg("--crate-type").arg(match crate_type {
                 CrateType::Library => "lib",
+                CrateType::ProcMacro => "proc-macro",
             });
         }
 
@@ -122,11 +152,86 @@ impl Compiler {
     /// `--edition` for `rustc` and `-frust-edition` for `gccrs`
     pub fn edition(mut self, edition: Edition) -> Compiler {
         match self.kind() {
-            Kind::Rust1 => self.cmd.arg("-frust-edition"),
-            Kind::RustcBootstrap => self.cmd.arg("--edition"),
+            Kind::Rust1 => self.cmd.arg(format!("-frust-edition={}", edition.to_str())),
+            Kind::RustcBootstrap => self.cmd.arg("--edition").arg(edition.to_str()),
+        };
+
+        self
+    }
+
+    /// Set the edition to use for a compiler invocation if one is given, or keep the
+    /// compiler's default edition (2015)
+    pub fn maybe_edition(self, edition: Option<Edition>) -> Compiler {
+        match edition {
+            Some(edition) => self.edition(edition),
+            None => self,
+        }
+    }
+
+    /// Pass extra flags to the compiler as-is. These flags are specific to each compiler
+    pub fn flags<T: AsRef<OsStr>>(mut self, flags: &[T]) -> Compiler {
+        self.cmd.args(flags);
+        self
+    }
+
+    /// Enable a configuration option, such as a test case's revision. This is equivalent
+    /// to `--cfg` for `rustc` and `-frust-cfg` for `gccrs`
+    pub fn cfg(mut self, cfg: &str) -> Compiler {
+        match self.kind() {
+            Kind::Rust1 => self.cmd.arg(format!("-frust-cfg={cfg}")),
+            Kind::RustcBootstrap => self.cmd.arg("--cfg").arg(cfg),
         };
 
-        self.cmd.arg(edition.to_str());
+        self
+    }
+
+    /// Add a directory to the library search path. This is equivalent to `-L` for both
+    /// `rustc` and `gccrs`
+    pub fn library_path(mut self, dir: &Path) -> Compiler {
+        self.cmd.arg("-L").arg(dir);
+        self
+    }
+
+    /// Make an external crate available under `name`. This is equivalent to `--extern` for
+    /// `rustc` and `-frust-extern` for `gccrs`
+    pub fn extern_crate(mut self, name: &str, path: &Path) -> Compiler {
+        match self.kind() {
+            Kind::Rust1 => self
+                .cmd
+                .arg(format!("-frust-extern={name}={}", path.display())),
+            Kind::RustcBootstrap => self
+                .cmd
+                .arg("--extern")
+                .arg(format!("{name}={}", path.display())),
+        };
+
+        self
+    }
+
+    /// Set the path of the file produced by the compiler. This is equivalent to `-o` for
+    /// both `rustc` and `gccrs`
+    pub fn output(mut self, path: &Path) -> Compiler {
+        self.cmd.arg("-o").arg(path);
+        self
+    }
+
+    /// Only parse the given file. This is equivalent to `-Z parse-only` for `rustc` and
+    /// `-fsyntax-only` for `gccrs`
+    pub fn parse_only(mut self) -> Compiler {
+        match self.kind() {
+            Kind::Rust1 => self.cmd.arg("-fsyntax-only"),
+            Kind::RustcBootstrap => self.cmd.arg("-Z").arg("parse-only"),
+        };
+
+        self
+    }
+
+    /// Run the compiler from `dir` rather than from the current directory. Some compilers
+    /// write their dumps to their working directory, so this keeps parallel invocations
+    /// from overwriting each other's dumps. Relative paths given to the compiler are
+    /// resolved from `dir`
+    pub fn current_dir(mut self, dir: &Path) -> Compiler {
+        self.cmd.current_dir(dir);
         self
     }
 
@@ -144,3 +249,21 @@ impl Compiler {
             .stdout(self.stderr.take().unwrap_or_else(Stdio::null))
     }
 }
+
+/// Get the version string of a compiler. Some GCC frontends print their version on
+/// the standard error, so both outputs are checked
+pub fn version(compiler: &Path) -> Option<String> {
+    let output = Command::new(compiler)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    [output.stdout, output.stderr].iter().find_map(|bytes| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(String::from)
+    })
+}
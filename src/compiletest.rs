@@ -0,0 +1,542 @@
+//! Parser for the headers used by `compiletest`, rustc's test harness, which describe what
+//! each test case of the rustc test-suite expects and how it should be compiled:
+//!
+//! ```text
+//! // check-pass
+//! // edition:2018
+//! // compile-flags: --cfg foo
+//! // ignore-windows
+//! // aux-build:helper.rs
+//! ```
+//!
+//! Newer versions of rustc use `//@` instead of `//` to introduce headers, which are both
+//! supported. Test cases declaring `// revisions: a b` are compiled once per revision, with
+//! `--cfg a` and `--cfg b`, and can have headers and error annotations specific to some of
+//! their revisions, such as `//[a] compile-flags: -O` or `//[b]~ ERROR`.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::{Compiler, Edition};
+use crate::error::Error;
+
+/// What `compiletest` expects from the compilation and execution of a test case
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    CheckPass,
+    BuildPass,
+    RunPass,
+    CheckFail,
+    BuildFail,
+    RunFail,
+}
+
+impl Mode {
+    fn from_directive(directive: &str) -> Option<Mode> {
+        match directive {
+            "check-pass" => Some(Mode::CheckPass),
+            "build-pass" => Some(Mode::BuildPass),
+            "run-pass" => Some(Mode::RunPass),
+            "check-fail" => Some(Mode::CheckFail),
+            "build-fail" => Some(Mode::BuildFail),
+            "run-fail" => Some(Mode::RunFail),
+            _ => None,
+        }
+    }
+
+    /// Is a compiler expected to accept the test case? Test cases which only fail at
+    /// runtime are valid Rust code
+    pub fn compiles(self) -> bool {
+        match self {
+            Mode::CheckPass | Mode::BuildPass | Mode::RunPass | Mode::RunFail => true,
+            Mode::CheckFail | Mode::BuildFail => false,
+        }
+    }
+}
+
+/// Conditions which hold on the machine running the adaptor, and which can be used in
+/// `ignore-*` and `only-*` headers
+fn host_conditions() -> Vec<&'static str> {
+    let pointer_width = if cfg!(target_pointer_width = "64") {
+        "64bit"
+    } else {
+        "32bit"
+    };
+    let env = if cfg!(target_env = "musl") {
+        "musl"
+    } else {
+        "gnu"
+    };
+
+    vec![
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY,
+        pointer_width,
+        env,
+    ]
+}
+
+/// Error expected by a rustc test case, through a `//~ ERROR` annotation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Line on which the error is expected, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+/// Headers of a rustc test case, for one of its revisions
+#[derive(Debug, Default)]
+pub struct Headers {
+    /// Revision the headers apply to, if the test case has revisions
+    pub revision: Option<String>,
+    pub mode: Option<Mode>,
+    pub edition: Option<Edition>,
+    /// Extra flags to give to `rustc`, without the edition
+    pub compile_flags: Vec<String>,
+    /// Auxiliary crates needed by the test case
+    pub aux_builds: Vec<String>,
+    /// Auxiliary crates needed by the test case which are procedural macros, from the
+    /// `proc-macro` header of recent test-suites. They are also part of `aux_builds`
+    pub proc_macros: Vec<String>,
+    /// Reason for which the test case cannot run on this machine
+    pub ignore: Option<String>,
+    /// `//~ ERROR` annotations of the test case
+    pub annotations: Vec<Annotation>,
+    /// Messages which must appear in the output of the test case, from `error-pattern`.
+    /// Some failing test cases use them instead of annotations
+    pub error_patterns: Vec<String>,
+}
+
+/// Split the revisions a comment applies to from the rest of the comment, e.g. `[a,b] foo`
+/// gives `(Some("a,b"), "foo")`
+fn split_revisions(comment: &str) -> (Option<&str>, &str) {
+    comment
+        .strip_prefix('[')
+        .and_then(|comment| comment.split_once(']'))
+        .map_or((None, comment), |(revisions, rest)| (Some(revisions), rest))
+}
+
+/// Does a comment which applies to `revisions` apply to `revision`? Comments without
+/// revisions apply to all revisions
+fn applies_to(revisions: Option<&str>, revision: Option<&str>) -> bool {
+    match (revisions, revision) {
+        (None, _) => true,
+        (Some(revisions), Some(revision)) => revisions
+            .split(',')
+            .any(|candidate| candidate.trim() == revision),
+        (Some(_), None) => false,
+    }
+}
+
+/// Kind of the diagnostic expected by an annotation, e.g. `ERROR` or `WARN`, followed by
+/// its message
+fn split_annotation_kind(annotation: &str) -> (&str, &str) {
+    let annotation = annotation.trim_start();
+    let end = annotation
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(annotation.len());
+
+    (&annotation[..end], annotation[end..].trim())
+}
+
+/// Position of the `//~` or `//[revision]~` annotation of a line, if it has one. The
+/// annotation spans until the end of the line
+pub fn annotation_start(line: &str) -> Option<usize> {
+    line.match_indices("//")
+        .map(|(idx, _)| idx)
+        .find(|idx| split_revisions(&line[idx + 2..]).1.starts_with('~'))
+}
+
+/// Find all the `//~ ERROR` annotations of a test case which apply to `revision`. The
+/// expected line of an annotation depends on its prefix:
+///
+/// - `//~ ERROR`: the line of the annotation
+/// - `//~^^ ERROR`: one line above the annotation per `^`
+/// - `//~v ERROR`: one line below the annotation per `v`
+/// - `//~| ERROR`: the same line as the previous annotation
+fn error_annotations(content: &str, revision: Option<&str>) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    // Line of the previous annotation, of any kind, for `//~|`
+    let mut previous_line = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_number = idx + 1;
+
+        let annotation = annotation_start(line).and_then(|idx| {
+            let (revisions, annotation) = split_revisions(&line[idx + 2..]);
+
+            annotation
+                .strip_prefix('~')
+                .filter(|_| applies_to(revisions, revision))
+        });
+        let Some(annotation) = annotation else {
+            continue;
+        };
+
+        let (expected_line, annotation) = if let Some(rest) = annotation.strip_prefix('|') {
+            (previous_line.unwrap_or(line_number), rest)
+        } else {
+            let above = annotation.chars().take_while(|&c| c == '^').count();
+            let below = annotation.chars().take_while(|&c| c == 'v').count();
+
+            (
+                (line_number + below).saturating_sub(above),
+                &annotation[above + below..],
+            )
+        };
+        previous_line = Some(expected_line);
+
+        if let ("ERROR", message) = split_annotation_kind(annotation) {
+            annotations.push(Annotation {
+                line: expected_line,
+                message: message.to_string(),
+            });
+        }
+    }
+
+    annotations
+}
+
+/// Split a header line into the revisions it applies to, its directive and optional value,
+/// e.g. `[a] compile-flags: -O` gives `(Some("a"), "compile-flags", Some("-O"))`. Returns
+/// `None` for comments which are not headers
+fn split_header(line: &str) -> Option<(Option<&str>, &str, Option<&str>)> {
+    let line = line.trim_start();
+    let comment = line
+        .strip_prefix("//@")
+        .or_else(|| line.strip_prefix("//"))?
+        .trim();
+    let (revisions, header) = split_revisions(comment);
+    let header = header.trim();
+
+    // Error annotations
+    if header.starts_with('~') {
+        return None;
+    }
+
+    let end = header
+        .find(|c: char| c == ':' || c.is_whitespace())
+        .unwrap_or(header.len());
+    let (directive, rest) = header.split_at(end);
+    let value = rest
+        .trim_start()
+        .strip_prefix(':')
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    Some((revisions, directive, value))
+}
+
+/// Remove the edition from a list of compile flags, returning it if present
+fn extract_edition(flags: &mut Vec<String>) -> Option<Edition> {
+    let idx = flags
+        .iter()
+        .position(|flag| flag.starts_with("--edition"))?;
+    let flag = flags.remove(idx);
+
+    match flag.strip_prefix("--edition=") {
+        Some(year) => Edition::from_year(year),
+        None if idx < flags.len() => Edition::from_year(&flags.remove(idx)),
+        None => None,
+    }
+}
+
+/// Lines of a test case which can contain headers. Like `compiletest`, stop looking for
+/// headers once the first item is reached
+fn header_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .take_while(|line| !line.starts_with("fn") && !line.starts_with("mod"))
+}
+
+/// Is a line of a test case a header? Comments such as `// check that foo compiles` are
+/// not headers: the whole comment must be the directive, optionally followed by a value
+pub fn is_header(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with("//@") {
+        return true;
+    }
+
+    let Some((_, directive, _)) = split_header(line) else {
+        return false;
+    };
+    let is_directive = !directive.is_empty()
+        && directive
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    let comment = split_revisions(line[2..].trim()).1.trim();
+
+    is_directive
+        && comment
+            .strip_prefix(directive)
+            .is_some_and(|rest| rest.is_empty() || rest.trim_start().starts_with(':'))
+}
+
+/// Amount of lines at the start of a test case which can contain headers, see
+/// [`is_header`]
+pub fn header_count(content: &str) -> usize {
+    header_lines(content).count()
+}
+
+impl Headers {
+    /// Parse the headers of each revision of a test case. Test cases without revisions
+    /// have a single set of headers, which does not belong to any revision
+    pub fn parse_revisions(content: &str) -> Vec<Headers> {
+        let revisions = header_lines(content).filter_map(split_header).find_map(
+            |(_, directive, value)| match (directive, value) {
+                ("revisions", Some(revisions)) => Some(revisions),
+                _ => None,
+            },
+        );
+
+        match revisions {
+            Some(revisions) => revisions
+                .split_whitespace()
+                .map(|revision| Headers::parse(content, Some(revision)))
+                .collect(),
+            None => vec![Headers::parse(content, None)],
+        }
+    }
+
+    /// Parse the headers of a test case which apply to `revision`
+    pub fn parse(content: &str, revision: Option<&str>) -> Headers {
+        let conditions = host_conditions();
+        let mut headers = Headers {
+            revision: revision.map(String::from),
+            annotations: error_annotations(content, revision),
+            ..Headers::default()
+        };
+
+        let header_lines = header_lines(content)
+            .filter_map(split_header)
+            .filter(|(revisions, _, _)| applies_to(*revisions, revision));
+
+        for (_, directive, value) in header_lines {
+            if let Some(mode) = Mode::from_directive(directive) {
+                headers.mode = Some(mode);
+                continue;
+            }
+
+            match (directive, value) {
+                ("compile-flags", Some(flags)) => headers
+                    .compile_flags
+                    .extend(flags.split_whitespace().map(String::from)),
+                ("edition", Some(year)) => headers.edition = Edition::from_year(year),
+                ("aux-build", Some(aux)) => headers.aux_builds.push(aux.to_string()),
+                ("proc-macro", Some(aux)) => {
+                    headers.aux_builds.push(aux.to_string());
+                    headers.proc_macros.push(aux.to_string());
+                }
+                ("error-pattern", Some(pattern)) => {
+                    headers.error_patterns.push(pattern.to_string())
+                }
+                ("ignore-test", _) => {
+                    headers.ignore = Some(String::from("ignored by `ignore-test`"))
+                }
+                (directive, _) => {
+                    let ignored = if let Some(condition) = directive.strip_prefix("ignore-") {
+                        conditions.contains(&condition)
+                    } else if let Some(condition) = directive.strip_prefix("only-") {
+                        !conditions.contains(&condition)
+                    } else {
+                        // We cannot check the requirements of the test case, so be conservative
+                        directive.starts_with("needs-")
+                    };
+
+                    if ignored && headers.ignore.is_none() {
+                        headers.ignore = Some(format!("ignored on this machine by `{directive}`"));
+                    }
+                }
+            }
+        }
+
+        if let Some(edition) = extract_edition(&mut headers.compile_flags) {
+            headers.edition = Some(edition);
+        }
+
+        headers
+    }
+
+    /// Display a test case's path along with the revision, if any, in the same format as
+    /// `compiletest`: `path/to/test.rs#revision`
+    pub fn display(&self, file: &Path) -> String {
+        match &self.revision {
+            Some(revision) => format!("{}#{revision}", file.display()),
+            None => file.display().to_string(),
+        }
+    }
+
+    /// Expected standard output of a test case, from `<test>.<revision>.run.stdout` or
+    /// `<test>.run.stdout`. Test cases without such a file do not check their output
+    pub fn expected_stdout(&self, file: &Path) -> Result<Option<String>, Error> {
+        let revision_file = self
+            .revision
+            .as_ref()
+            .map(|revision| file.with_extension(format!("{revision}.run.stdout")));
+
+        match revision_file
+            .into_iter()
+            .chain(Some(file.with_extension("run.stdout")))
+            .find(|path| path.exists())
+        {
+            Some(path) => Ok(Some(fs::read_to_string(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Configure a compiler invocation for the test case's revision, if any
+    pub fn maybe_cfg(&self, compiler: Compiler) -> Compiler {
+        match &self.revision {
+            Some(revision) => compiler.cfg(revision),
+            None => compiler,
+        }
+    }
+
+    /// Is a compiler expected to accept the test case? Like in `compiletest`, UI test cases
+    /// which do not specify a mode are `check-fail` test cases, whether they check their
+    /// errors with annotations or with `error-pattern`
+    pub fn expects_success(&self) -> bool {
+        self.mode.is_some_and(Mode::compiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_header_directives() {
+        assert_eq!(
+            split_header("// check-pass"),
+            Some((None, "check-pass", None))
+        );
+        assert_eq!(
+            split_header("// edition:2018"),
+            Some((None, "edition", Some("2018")))
+        );
+        assert_eq!(
+            split_header("//@ compile-flags: --cfg foo -O"),
+            Some((None, "compile-flags", Some("--cfg foo -O")))
+        );
+        assert_eq!(
+            split_header("  //[a,b] compile-flags : -O"),
+            Some((Some("a,b"), "compile-flags", Some("-O")))
+        );
+        // Empty values are not values
+        assert_eq!(
+            split_header("// error-pattern:"),
+            Some((None, "error-pattern", None))
+        );
+    }
+
+    #[test]
+    fn split_header_non_headers() {
+        assert_eq!(split_header("fn main() {}"), None);
+        assert_eq!(split_header("//~ ERROR mismatched types"), None);
+        assert_eq!(split_header("//[a]~^ ERROR mismatched types"), None);
+    }
+
+    #[test]
+    fn is_header_directives() {
+        assert!(is_header("// check-pass"));
+        assert!(is_header("// run-pass"));
+        assert!(is_header("// aux-build:helper.rs"));
+        assert!(is_header("// compile-flags: --cfg foo"));
+        assert!(is_header("//[a] compile-flags: -O"));
+        assert!(is_header("// ignore-windows"));
+        assert!(is_header("//@ anything goes"));
+    }
+
+    #[test]
+    fn is_header_comments() {
+        assert!(!is_header("// check that foo compiles"));
+        assert!(!is_header("// Regression test for #1234."));
+        assert!(!is_header("// FIXME: this should be an error"));
+        assert!(!is_header("//~ ERROR mismatched types"));
+        assert!(!is_header("fn main() {}"));
+        assert!(!is_header("//"));
+    }
+
+    fn annotation(line: usize, message: &str) -> Annotation {
+        Annotation {
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn error_annotations_lines() {
+        let content = "\
+fn main() {
+    let x: u8 = \"\"; //~ ERROR mismatched types
+    foo();
+    //~^ ERROR cannot find function
+    //~| ERROR second error
+    //~^^^^ ERROR two lines up
+    //~v ERROR next line
+    bar();
+}
+";
+
+        assert_eq!(
+            error_annotations(content, None),
+            [
+                annotation(2, "mismatched types"),
+                annotation(3, "cannot find function"),
+                annotation(3, "second error"),
+                annotation(2, "two lines up"),
+                annotation(8, "next line"),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_annotations_kinds() {
+        let content = "\
+fn main() {} //~ WARN unused
+//~| ERROR follows the warning
+//~ NOTE some note
+//~ ERROR
+";
+
+        assert_eq!(
+            error_annotations(content, None),
+            [annotation(1, "follows the warning"), annotation(4, "")]
+        );
+    }
+
+    #[test]
+    fn error_annotations_revisions() {
+        let content = "\
+fn main() {} //[a]~ ERROR only a
+//[a,b]~^ ERROR a and b
+//[b]~ ERROR only b
+//~ ERROR all revisions
+";
+
+        assert_eq!(
+            error_annotations(content, Some("a")),
+            [
+                annotation(1, "only a"),
+                annotation(1, "a and b"),
+                annotation(4, "all revisions"),
+            ]
+        );
+        assert_eq!(
+            error_annotations(content, Some("b")),
+            [
+                annotation(1, "a and b"),
+                annotation(3, "only b"),
+                annotation(4, "all revisions"),
+            ]
+        );
+        // Annotations restricted to revisions do not apply to test cases without revisions
+        assert_eq!(
+            error_annotations(content, None),
+            [annotation(4, "all revisions")]
+        );
+    }
+}
@@ -0,0 +1,638 @@
+//! Parser for the DejaGnu directives used by the test cases of the gccrs test-suite, which
+//! describe what each test case expects and how it should be compiled:
+//!
+//! ```text
+//! // { dg-do run { target x86_64-*-* } }
+//! // { dg-options "-O2 -frust-edition=2018" }
+//! // { dg-additional-options "-w" }
+//! // { dg-error "mismatched types" "" { target *-*-* } .-1 }
+//! // { dg-skip-if "needs 64 bits" { ! lp64 } }
+//! /* { dg-output "hello\r*\n" } */
+//! ```
+//!
+//! Directives are Tcl lists, which are split the same way Tcl does: words are separated by
+//! whitespace, and can be grouped with double quotes, which substitute backslash escapes,
+//! or braces, which are kept as-is and can be nested. Directives restricted to other
+//! targets with a `{ target ... }` selector are ignored.
+
+use std::path::Path;
+
+use glob::Pattern;
+use regex::Regex;
+
+use crate::auxiliary;
+use crate::compiler::Edition;
+
+/// Start of a directive, e.g. `{ dg-error`
+const DIRECTIVE_START: &str = r"\{\s*dg-";
+
+/// What the test harness does with a test case, from `dg-do`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Action {
+    Preprocess,
+    #[default]
+    Compile,
+    Assemble,
+    Link,
+    Run,
+}
+
+impl Action {
+    fn from_directive(action: &str) -> Option<Action> {
+        match action {
+            "preprocess" => Some(Action::Preprocess),
+            "compile" => Some(Action::Compile),
+            "assemble" => Some(Action::Assemble),
+            "link" => Some(Action::Link),
+            "run" => Some(Action::Run),
+            _ => None,
+        }
+    }
+
+    /// Action of test cases which do not have a `dg-do` directive. Like in the gccrs
+    /// test-suite, test cases in an `execute` directory are run, the ones in the `link`
+    /// directory linked with their dependencies, and the others compiled
+    pub fn default_for(file: &Path) -> Action {
+        if file
+            .components()
+            .any(|component| component.as_os_str() == "execute")
+        {
+            Action::Run
+        } else if auxiliary::is_link_test(file) {
+            Action::Link
+        } else {
+            Action::Compile
+        }
+    }
+
+    /// Does the action produce an executable? Test cases which are only compiled do not
+    /// need a `main` function
+    pub fn links(self) -> bool {
+        matches!(self, Action::Link | Action::Run)
+    }
+}
+
+/// Kind of diagnostic expected by a directive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// `dg-error`
+    Error,
+    /// `dg-warning`
+    Warning,
+    /// `dg-message` or `dg-note`
+    Message,
+    /// `dg-bogus`, which is a diagnostic the compiler must *not* report
+    Bogus,
+}
+
+impl DiagnosticKind {
+    fn from_directive(directive: &str) -> Option<DiagnosticKind> {
+        match directive {
+            "dg-error" => Some(DiagnosticKind::Error),
+            "dg-warning" => Some(DiagnosticKind::Warning),
+            "dg-message" | "dg-note" => Some(DiagnosticKind::Message),
+            "dg-bogus" => Some(DiagnosticKind::Bogus),
+            _ => None,
+        }
+    }
+}
+
+/// Diagnostic expected by a test case
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expectation {
+    pub kind: DiagnosticKind,
+    /// Line on which the diagnostic is expected, starting at 1. Diagnostics without a line
+    /// can be reported anywhere
+    pub line: Option<usize>,
+    /// Regular expression matching the diagnostic's message
+    pub pattern: String,
+    /// Is the compiler known not to report the diagnostic yet?
+    pub xfail: bool,
+}
+
+/// Everything the directives of a gccrs test case expect from it
+#[derive(Debug, Default)]
+pub struct Spec {
+    pub action: Action,
+    /// Is the action known to fail, through a `{ xfail ... }` selector?
+    pub xfail: bool,
+    /// Options from `dg-options` and `dg-additional-options`
+    pub options: Vec<String>,
+    pub diagnostics: Vec<Expectation>,
+    /// Regular expressions the output of the test case must match, from `dg-output`. They
+    /// are concatenated, and must match the whole output in order
+    pub output: Vec<String>,
+    /// Other files compiled along with the test case, from `dg-additional-sources`
+    pub additional_sources: Vec<String>,
+    /// Reason for which the test case cannot run on this machine
+    pub skip: Option<String>,
+}
+
+/// Target triplet of the machine running the adaptor, in the format used by DejaGnu,
+/// such as `x86_64-pc-linux-gnu`
+fn host_triplet() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i686",
+        arch => arch,
+    };
+
+    match std::env::consts::OS {
+        "linux" if arch.starts_with('x') || arch.starts_with('i') => format!("{arch}-pc-linux-gnu"),
+        "linux" => format!("{arch}-unknown-linux-gnu"),
+        "macos" => format!("{arch}-apple-darwin"),
+        os => format!("{arch}-unknown-{os}"),
+    }
+}
+
+/// Replace Tcl's backslash escapes by the character they represent
+fn substitute_escapes(word: &str) -> String {
+    let mut substituted = String::with_capacity(word.len());
+    let mut chars = word.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            substituted.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => substituted.push('\n'),
+            Some('r') => substituted.push('\r'),
+            Some('t') => substituted.push('\t'),
+            Some(c) => substituted.push(c),
+            None => substituted.push('\\'),
+        }
+    }
+
+    substituted
+}
+
+/// Length of the braced word starting at the beginning of `s`, including its braces.
+/// Returns `None` if the braces are not balanced
+fn braced_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for (idx, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Split a Tcl list into its words, e.g. `dg-error "foo bar" { target *-*-* } 12` gives
+/// `["dg-error", "foo bar", " target *-*-* ", "12"]`
+fn split_list(list: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut rest = list.trim_start();
+
+    while !rest.is_empty() {
+        let len = if let Some(braced) = rest.strip_prefix('{') {
+            match braced_len(rest) {
+                Some(len) => {
+                    words.push(braced[..len - 2].to_string());
+                    len
+                }
+                None => {
+                    words.push(braced.to_string());
+                    rest.len()
+                }
+            }
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let mut escaped = false;
+            let end = quoted
+                .find(|c| {
+                    let end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    end
+                })
+                .unwrap_or(quoted.len());
+            words.push(substitute_escapes(&quoted[..end]));
+            (end + 2).min(rest.len())
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            words.push(substitute_escapes(&rest[..end]));
+            end
+        };
+
+        rest = rest[len..].trim_start();
+    }
+
+    words
+}
+
+/// Does a single target match the host? Targets are either triplet patterns, such as
+/// `x86_64-*-linux*`, or effective target keywords. Unknown keywords never match
+fn matches_target(target: &str, triplet: &str) -> bool {
+    match target {
+        "*" | "native" => true,
+        "lp64" => cfg!(target_pointer_width = "64"),
+        "ilp32" => cfg!(target_pointer_width = "32"),
+        target if target.contains('-') => {
+            Pattern::new(target).is_ok_and(|pattern| pattern.matches(triplet))
+        }
+        _ => false,
+    }
+}
+
+/// Evaluate a single term of a target selector expression, which can be negated with `!`.
+/// Braced sub-expressions are kept as a single word, and evaluated recursively
+fn matches_term<'a>(
+    word: &'a str,
+    words: &mut impl Iterator<Item = &'a String>,
+    triplet: &str,
+) -> bool {
+    if word == "!" {
+        return match words.next() {
+            Some(next) => !matches_term(next, words, triplet),
+            None => false,
+        };
+    }
+
+    let word = word.trim();
+    if word.contains(char::is_whitespace) {
+        matches_selector(&split_list(word), triplet)
+    } else {
+        matches_target(word, triplet)
+    }
+}
+
+/// Evaluate a target selector expression, such as `x86_64-*-* i?86-*-*` or
+/// `{ ! lp64 } && *-*-linux*`. Targets which are not separated by an operator match if
+/// any of them does. Operators are evaluated from left to right
+fn matches_selector(words: &[String], triplet: &str) -> bool {
+    let mut result = false;
+    let mut and = false;
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+        match word.as_str() {
+            "||" => and = false,
+            "&&" => and = true,
+            word => {
+                let value = matches_term(word, &mut words, triplet);
+                result = if and {
+                    result && value
+                } else {
+                    result || value
+                };
+                and = false;
+            }
+        }
+    }
+
+    result
+}
+
+/// Selector restricting a directive, such as `{ target *-*-linux* }` or `{ xfail lp64 }`
+enum Selector {
+    /// Does the directive apply to the host?
+    Target(bool),
+    /// Is the directive known to fail on the host?
+    XFail(bool),
+}
+
+impl Selector {
+    fn parse(selector: &str, triplet: &str) -> Option<Selector> {
+        let words = split_list(selector);
+        let (kind, targets) = words.split_first()?;
+
+        match kind.as_str() {
+            "target" => Some(Selector::Target(matches_selector(targets, triplet))),
+            "xfail" => Some(Selector::XFail(matches_selector(targets, triplet))),
+            _ => None,
+        }
+    }
+}
+
+/// Does a directive apply to the host, and is it known to fail there?
+fn applies(selector: Option<&String>, triplet: &str) -> (bool, bool) {
+    match selector.and_then(|selector| Selector::parse(selector, triplet)) {
+        Some(Selector::Target(applies)) => (applies, false),
+        Some(Selector::XFail(xfail)) => (true, xfail),
+        None => (true, false),
+    }
+}
+
+/// Resolve the line of a diagnostic directive located on `line`, which can be absolute,
+/// relative to the directive (`.`, `.-1`, `.+2`), or `0` for diagnostics without a line
+fn expected_line(spec: Option<&String>, line: usize) -> Option<usize> {
+    let Some(spec) = spec.map(|spec| spec.trim()) else {
+        return Some(line);
+    };
+
+    match spec.strip_prefix('.') {
+        Some("") => Some(line),
+        Some(offset) => {
+            let (sign, offset) = offset.split_at(1);
+            let offset: usize = offset.parse().ok()?;
+
+            match sign {
+                "+" => Some(line + offset),
+                "-" => line.checked_sub(offset),
+                _ => None,
+            }
+        }
+        None => spec.parse().ok().filter(|&line| line != 0),
+    }
+}
+
+/// Find all the directives of a test case, along with the line they are on
+fn directives(content: &str) -> Vec<(usize, Vec<String>)> {
+    let start = Regex::new(DIRECTIVE_START).expect("invalid directive regex");
+
+    content
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            start.find_iter(line).filter_map(move |found| {
+                let directive = &line[found.start()..];
+                let len = braced_len(directive)?;
+
+                Some((idx + 1, split_list(&directive[1..len - 1])))
+            })
+        })
+        .collect()
+}
+
+/// Do the options given to a test case match any of the patterns of a `dg-skip-if`
+/// directive? An empty list of patterns, or `*`, matches any options
+fn matches_options(patterns: Option<&String>, options: &[String], default: bool) -> bool {
+    let patterns = match patterns {
+        Some(patterns) => split_list(patterns),
+        None => return default,
+    };
+
+    match patterns.as_slice() {
+        [] => default,
+        [pattern] if pattern == "*" => true,
+        patterns => patterns.iter().any(|pattern| {
+            Pattern::new(pattern)
+                .is_ok_and(|pattern| options.iter().any(|option| pattern.matches(option)))
+        }),
+    }
+}
+
+impl Spec {
+    /// Parse the directives of a test case. `default` is the action used if the test case
+    /// does not have a `dg-do` directive, see [`Action::default_for`]
+    pub fn parse(content: &str, default: Action) -> Spec {
+        let triplet = host_triplet();
+        let mut spec = Spec {
+            action: default,
+            ..Spec::default()
+        };
+        // `dg-skip-if` directives depend on the options of the test case, which are only
+        // all known once every directive has been parsed
+        let mut skip_ifs = Vec::new();
+
+        for (line, words) in directives(content) {
+            let Some((directive, args)) = words.split_first() else {
+                continue;
+            };
+
+            match directive.as_str() {
+                "dg-do" => {
+                    let (applies, xfail) = applies(args.get(1), &triplet);
+
+                    match args
+                        .first()
+                        .and_then(|action| Action::from_directive(action))
+                    {
+                        Some(action) if applies => {
+                            spec.action = action;
+                            spec.xfail = xfail;
+                        }
+                        Some(_) => {
+                            spec.skip = Some(format!("`dg-do` is not supported on {triplet}"))
+                        }
+                        None => {}
+                    }
+                }
+                "dg-options" | "dg-additional-options" => {
+                    if !applies(args.get(1), &triplet).0 {
+                        continue;
+                    }
+                    if directive == "dg-options" {
+                        spec.options.clear();
+                    }
+                    if let Some(options) = args.first() {
+                        spec.options
+                            .extend(options.split_whitespace().map(String::from));
+                    }
+                }
+                "dg-output" => {
+                    if let (Some(pattern), (true, _)) =
+                        (args.first(), applies(args.get(1), &triplet))
+                    {
+                        spec.output.push(pattern.clone());
+                    }
+                }
+                "dg-additional-sources" => {
+                    if let Some(sources) = args.first() {
+                        spec.additional_sources
+                            .extend(sources.split_whitespace().map(String::from));
+                    }
+                }
+                "dg-skip-if" => skip_ifs.push(args.to_vec()),
+                "dg-require-effective-target" => {
+                    if let Some(target) = args.first() {
+                        if !matches_target(target, &triplet) && spec.skip.is_none() {
+                            spec.skip = Some(format!("requires effective target `{target}`"));
+                        }
+                    }
+                }
+                directive => {
+                    let (Some(kind), Some(pattern)) =
+                        (DiagnosticKind::from_directive(directive), args.first())
+                    else {
+                        continue;
+                    };
+                    let (applies, xfail) = applies(args.get(2), &triplet);
+
+                    if applies {
+                        spec.diagnostics.push(Expectation {
+                            kind,
+                            line: expected_line(args.get(3), line),
+                            pattern: pattern.clone(),
+                            xfail,
+                        });
+                    }
+                }
+            }
+        }
+
+        // `dg-skip-if "comment" { selector } { include-opts } { exclude-opts }`
+        for args in skip_ifs {
+            let selected = args
+                .get(1)
+                .is_some_and(|selector| matches_selector(&split_list(selector), &triplet));
+            let included = matches_options(args.get(2), &spec.options, true);
+            let excluded = matches_options(args.get(3), &spec.options, false);
+
+            if selected && included && !excluded && spec.skip.is_none() {
+                let reason = args.first().filter(|reason| !reason.is_empty());
+                spec.skip = Some(match reason {
+                    Some(reason) => format!("skipped by `dg-skip-if`: {reason}"),
+                    None => String::from("skipped by `dg-skip-if`"),
+                });
+            }
+        }
+
+        spec
+    }
+
+    /// Does the test case expect the compiler to reject it? Errors which the compiler is
+    /// known not to report yet still make the test case invalid Rust code
+    pub fn expects_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|expectation| expectation.kind == DiagnosticKind::Error)
+    }
+
+    /// Edition requested through `-frust-edition`, if any
+    pub fn edition(&self) -> Option<Edition> {
+        self.options
+            .iter()
+            .rev()
+            .find_map(|option| option.strip_prefix("-frust-edition="))
+            .and_then(Edition::from_year)
+    }
+
+    /// Regular expression the output of the test case must match, if it has any
+    /// `dg-output` directive
+    pub fn output_pattern(&self) -> Option<String> {
+        if self.output.is_empty() {
+            None
+        } else {
+            Some(self.output.concat())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str = "x86_64-pc-linux-gnu";
+
+    #[test]
+    fn split_list_words() {
+        assert_eq!(split_list("dg-do  run"), ["dg-do", "run"]);
+        assert_eq!(split_list("  dg-do run  "), ["dg-do", "run"]);
+        assert!(split_list("   ").is_empty());
+    }
+
+    #[test]
+    fn split_list_quoted() {
+        assert_eq!(
+            split_list(r#"dg-error "foo bar" "" 12"#),
+            ["dg-error", "foo bar", "", "12"]
+        );
+        assert_eq!(split_list(r#""a \"quoted\" word""#), [r#"a "quoted" word"#]);
+        assert_eq!(split_list(r#""hello\r*\n""#), ["hello\r*\n"]);
+        // An unterminated quote spans until the end of the list
+        assert_eq!(split_list(r#"dg-error "foo"#), ["dg-error", "foo"]);
+    }
+
+    #[test]
+    fn split_list_braced() {
+        assert_eq!(
+            split_list(r#"dg-error "mismatched" "" { target *-*-* } .-1"#),
+            ["dg-error", "mismatched", "", " target *-*-* ", ".-1"]
+        );
+        // Braces keep their content as-is, including nested braces and escapes
+        assert_eq!(
+            split_list(r"{ target { ! lp64 } } {\n}"),
+            [" target { ! lp64 } ", r"\n"]
+        );
+        // Unbalanced braces span until the end of the list
+        assert_eq!(
+            split_list("dg-do run { target"),
+            ["dg-do", "run", " target"]
+        );
+    }
+
+    #[test]
+    fn selector_targets() {
+        assert!(matches_selector(&split_list("*-*-*"), LINUX));
+        assert!(matches_selector(&split_list("x86_64-*-linux*"), LINUX));
+        assert!(!matches_selector(&split_list("arm*-*-*"), LINUX));
+        // Targets without an operator match if any of them does
+        assert!(matches_selector(&split_list("arm*-*-* x86_64-*-*"), LINUX));
+        assert!(!matches_selector(&split_list("arm*-*-* i?86-*-*"), LINUX));
+        // Unknown effective target keywords never match
+        assert!(!matches_selector(
+            &split_list("rust_unknown_keyword"),
+            LINUX
+        ));
+        assert!(!matches_selector(&[], LINUX));
+    }
+
+    #[test]
+    fn selector_operators() {
+        assert!(!matches_selector(&split_list("! *-*-linux*"), LINUX));
+        assert!(matches_selector(&split_list("! arm*-*-*"), LINUX));
+        assert!(matches_selector(
+            &split_list("*-*-linux* && x86_64-*-*"),
+            LINUX
+        ));
+        assert!(!matches_selector(
+            &split_list("*-*-linux* && arm*-*-*"),
+            LINUX
+        ));
+        assert!(matches_selector(
+            &split_list("arm*-*-* || *-*-linux*"),
+            LINUX
+        ));
+        // Braced sub-expressions are evaluated recursively
+        assert!(!matches_selector(
+            &split_list("{ ! *-*-linux* } && *-*-*"),
+            LINUX
+        ));
+        assert!(matches_selector(
+            &split_list("{ arm*-*-* || *-*-linux* } && x86_64-*-*"),
+            LINUX
+        ));
+    }
+
+    #[test]
+    fn selector_pointer_width() {
+        let lp64 = cfg!(target_pointer_width = "64");
+
+        assert_eq!(matches_selector(&split_list("lp64"), LINUX), lp64);
+        assert_eq!(matches_selector(&split_list("{ ! lp64 }"), LINUX), !lp64);
+        assert_eq!(matches_selector(&split_list("ilp32"), LINUX), !lp64);
+    }
+
+    #[test]
+    fn expected_line_absolute() {
+        assert_eq!(expected_line(None, 7), Some(7));
+        assert_eq!(expected_line(Some(&String::from("12")), 7), Some(12));
+        assert_eq!(expected_line(Some(&String::from(" 12 ")), 7), Some(12));
+        // Diagnostics which are not located on a line
+        assert_eq!(expected_line(Some(&String::from("0")), 7), None);
+        assert_eq!(expected_line(Some(&String::from("foo")), 7), None);
+    }
+
+    #[test]
+    fn expected_line_relative() {
+        assert_eq!(expected_line(Some(&String::from(".")), 7), Some(7));
+        assert_eq!(expected_line(Some(&String::from(".-1")), 7), Some(6));
+        assert_eq!(expected_line(Some(&String::from(".+2")), 7), Some(9));
+        assert_eq!(expected_line(Some(&String::from(".-8")), 7), None);
+        assert_eq!(expected_line(Some(&String::from(".*1")), 7), None);
+        assert_eq!(expected_line(Some(&String::from(".-")), 7), None);
+    }
+}
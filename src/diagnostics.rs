@@ -0,0 +1,300 @@
+//! Comparison of the errors reported by a compiler with the ones expected by a rustc test
+//! case, either through its `//~ ERROR` annotations or its `.stderr` golden file. Errors
+//! are extracted from the compiler's standard error, which uses GCC's
+//! `file:line:column: error: message [E0308]` format, and matched with the expected ones
+//! by line.
+
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::compiletest::Annotation;
+
+/// Error in GCC's diagnostic format. The column and the error code are optional
+const GCC_ERROR: &str = r"^(.+?):(\d+):(?:\d+:)? (?:fatal )?error: (.*?)(?: \[(E\d{4})\])?$";
+
+/// Start of an error in rustc's diagnostic format, e.g. `error[E0308]: mismatched types`
+const RUSTC_ERROR: &str = r"^error(?:\[(E\d{4})\])?: (.*)$";
+
+/// Primary location of a diagnostic in rustc's format, e.g. `  --> $DIR/foo.rs:4:9`
+const RUSTC_LOCATION: &str = r"^\s*--> (.+):(\d+):\d+$";
+
+/// Error reported by a compiler
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+}
+
+/// Extract the errors located in `file` from a compiler's standard error, in GCC's format
+fn gcc_errors(stderr: &str, file: &str) -> Vec<Diagnostic> {
+    let error = Regex::new(GCC_ERROR).expect("invalid GCC error regex");
+
+    stderr
+        .lines()
+        .filter_map(|line| error.captures(line))
+        .filter(|captures| &captures[1] == file)
+        .filter_map(|captures| {
+            Some(Diagnostic {
+                line: captures[2].parse().ok()?,
+                code: captures.get(4).map(|code| code.as_str().to_string()),
+                message: captures[3].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Extract the errors located in the test case named `file_name` from a rustc `.stderr`
+/// golden file. Paths in these files are relative to `$DIR`, the directory of the test
+/// case. Errors without a location, such as `error: aborting due to 2 previous errors`,
+/// are ignored
+pub fn rustc_errors(stderr: &str, file_name: &str) -> Vec<Diagnostic> {
+    let error = Regex::new(RUSTC_ERROR).expect("invalid rustc error regex");
+    let location = Regex::new(RUSTC_LOCATION).expect("invalid rustc location regex");
+    let test_path = format!("$DIR/{file_name}");
+
+    let mut errors = Vec::new();
+    // Error waiting for its primary location
+    let mut pending = None;
+
+    for line in stderr.lines() {
+        if let Some(captures) = error.captures(line) {
+            pending = Some((
+                captures.get(1).map(|code| code.as_str().to_string()),
+                captures[2].to_string(),
+            ));
+        } else if let Some(captures) = location.captures(line) {
+            if let Some((code, message)) = pending.take() {
+                if captures[1] == test_path {
+                    errors.push(Diagnostic {
+                        line: captures[2].parse().unwrap_or_default(),
+                        code,
+                        message,
+                    });
+                }
+            }
+        } else if !line.starts_with(' ') && !line.is_empty() {
+            // Any other diagnostic, such as a warning, ends the current one
+            pending = None;
+        }
+    }
+
+    errors
+}
+
+/// Errors a compiler is expected to report when compiling a test case
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExpectedErrors {
+    /// File in which the errors are expected, as given to the compiler. Errors reported in
+    /// other files, such as modules of the test case, are ignored
+    pub file: String,
+    pub annotations: Vec<Annotation>,
+}
+
+/// Result of a single annotation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnotationResult {
+    #[serde(flatten)]
+    pub annotation: Annotation,
+    /// Did the compiler report an error on the annotation's line?
+    pub found: bool,
+}
+
+/// Result of matching the errors reported by a compiler with the annotations of a test
+/// case
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub annotations: Vec<AnnotationResult>,
+    /// Lines on which the compiler reported errors which were not annotated
+    pub unexpected: Vec<usize>,
+}
+
+/// Lines of `file` on which errors are reported in a compiler's standard error
+fn error_lines(stderr: &str, file: &str) -> Vec<usize> {
+    let mut lines: Vec<usize> = gcc_errors(stderr, file)
+        .into_iter()
+        .map(|error| error.line)
+        .collect();
+
+    lines.sort_unstable();
+    lines.dedup();
+
+    lines
+}
+
+impl Diagnostics {
+    pub fn new(expected: &ExpectedErrors, stderr: &str) -> Diagnostics {
+        let reported = error_lines(stderr, &expected.file);
+
+        Diagnostics {
+            annotations: expected
+                .annotations
+                .iter()
+                .map(|annotation| AnnotationResult {
+                    annotation: annotation.clone(),
+                    found: reported.contains(&annotation.line),
+                })
+                .collect(),
+            unexpected: reported
+                .into_iter()
+                .filter(|line| {
+                    !expected
+                        .annotations
+                        .iter()
+                        .any(|annotation| annotation.line == *line)
+                })
+                .collect(),
+        }
+    }
+
+    /// Were all the annotations found, without any unexpected error?
+    pub fn matches(&self) -> bool {
+        self.unexpected.is_empty() && self.annotations.iter().all(|result| result.found)
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let found = self
+            .annotations
+            .iter()
+            .filter(|result| result.found)
+            .count();
+
+        write!(
+            f,
+            "found {} out of {} annotated errors, and {} unexpected errors",
+            found,
+            self.annotations.len(),
+            self.unexpected.len()
+        )?;
+
+        if !self.unexpected.is_empty() {
+            let lines: Vec<String> = self.unexpected.iter().map(usize::to_string).collect();
+            write!(f, " (lines {})", lines.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors reported by rustc for a test case, as found in its `.stderr` golden file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferenceErrors {
+    /// File in which the errors are expected, as given to the compiler
+    pub file: String,
+    pub errors: Vec<Diagnostic>,
+}
+
+/// Amount of elements two lists have in common, counting duplicates
+fn common<T: Ord>(mut lhs: Vec<T>, mut rhs: Vec<T>) -> usize {
+    lhs.sort_unstable();
+    rhs.sort_unstable();
+
+    let (mut lhs, mut rhs) = (lhs.into_iter().peekable(), rhs.into_iter().peekable());
+    let mut count = 0;
+
+    while let (Some(l), Some(r)) = (lhs.peek(), rhs.peek()) {
+        match l.cmp(r) {
+            Ordering::Less => {
+                lhs.next();
+            }
+            Ordering::Greater => {
+                rhs.next();
+            }
+            Ordering::Equal => {
+                count += 1;
+                lhs.next();
+                rhs.next();
+            }
+        }
+    }
+
+    count
+}
+
+/// Ratio between the amount of matching elements and the size of the largest list. Two
+/// empty lists match perfectly
+fn ratio(matching: usize, lhs: usize, rhs: usize) -> f64 {
+    match lhs.max(rhs) {
+        0 => 1.0,
+        max => matching as f64 / max as f64,
+    }
+}
+
+/// How closely the errors reported by `gccrs` match the ones reported by rustc. Each
+/// criteria is scored between 0 and 1, and the final score is their average
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Score {
+    /// Amount of errors reported by rustc
+    pub expected: usize,
+    /// Amount of errors reported by `gccrs`
+    pub reported: usize,
+    /// Amount of errors reported on the same line by both compilers
+    pub matching_lines: usize,
+    /// Amount of error codes reported by both compilers
+    pub matching_codes: usize,
+    pub score: f64,
+}
+
+impl Score {
+    pub fn new(reference: &ReferenceErrors, stderr: &str) -> Score {
+        let expected = &reference.errors;
+        let reported = gcc_errors(stderr, &reference.file);
+
+        let lines = |errors: &[Diagnostic]| -> Vec<usize> {
+            errors.iter().map(|error| error.line).collect()
+        };
+        let codes = |errors: &[Diagnostic]| -> Vec<String> {
+            errors
+                .iter()
+                .filter_map(|error| error.code.clone())
+                .collect()
+        };
+
+        let matching_lines = common(lines(expected), lines(&reported));
+        let matching_codes = common(codes(expected), codes(&reported));
+
+        let criteria = [
+            ratio(
+                expected.len().min(reported.len()),
+                expected.len(),
+                reported.len(),
+            ),
+            ratio(matching_lines, expected.len(), reported.len()),
+            ratio(
+                matching_codes,
+                codes(expected).len(),
+                codes(&reported).len(),
+            ),
+        ];
+        let score = criteria.iter().sum::<f64>() / criteria.len() as f64;
+
+        Score {
+            expected: expected.len(),
+            reported: reported.len(),
+            matching_lines,
+            matching_codes,
+            score,
+        }
+    }
+
+    /// Did `gccrs` report the same errors as rustc?
+    pub fn is_perfect(&self) -> bool {
+        self.score >= 1.0
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "diagnostics score {:.2}: {} errors reported out of {}, {} on matching lines, {} matching error codes",
+            self.score, self.reported, self.expected, self.matching_lines, self.matching_codes
+        )
+    }
+}
@@ -26,6 +26,12 @@ pub enum Error {
     Misc(MiscKind),
     #[error("invalid exit code: expected value to fit in `u8`: {0}")]
     ExitCodeConversion(TryFromIntError),
+    #[error("invalid YAML test-suite: {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("invalid JSON report: {0}")]
+    Json(serde_json::Error),
+    #[error("found {0} regression(s)")]
+    Regressions(usize),
 }
 
 impl From<std::io::Error> for Error {
@@ -51,3 +57,15 @@ impl From<TryFromIntError> for Error {
         Error::ExitCodeConversion(e)
     }
 }
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Error::Yaml(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,297 @@
+//! Conversion of rustc test cases which `gccrs` already handles into DejaGnu test cases,
+//! so that they can be added to the gccrs test-suite. Test cases are selected from the
+//! JSON report of a test run: rustc test cases compiled by `gccrs` (`gccrs-rustc-success`)
+//! or whose errors were matched (`gccrs-rustc-errors`) become `compile` test cases, and
+//! the ones whose binary behaved like rustc's (`gccrs-rustc-run`) become `execute` test
+//! cases. Their compiletest headers and annotations are rewritten into DejaGnu directives,
+//! and they are written into a staging tree mirroring `gcc/testsuite/rust`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::compiletest::{self, Headers, Mode};
+use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::modules;
+use crate::passes::PassKind;
+use crate::report::Report;
+use crate::runner::Outcome;
+
+/// Directory of the gccrs test-suite, relative to the root of the gccrs repository
+const TESTSUITE: &str = "gcc/testsuite/rust";
+
+/// Kind of DejaGnu test case a rustc test case is converted to. `execute` test cases are
+/// also compiled, so they are preferred when a test case passes both kinds of passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Compile,
+    Execute,
+}
+
+impl Kind {
+    /// Kind of test case matching the passes of a test run, if any
+    fn from_pass(pass: &str) -> Option<Kind> {
+        if pass == PassKind::GccrsRustcSucess.to_string()
+            || pass == PassKind::GccrsRustcErrors.to_string()
+        {
+            Some(Kind::Compile)
+        } else if pass == PassKind::GccrsRustcRun.to_string() {
+            Some(Kind::Execute)
+        } else {
+            None
+        }
+    }
+
+    /// Directory of the test-suite containing this kind of test cases
+    fn dir(self) -> PathBuf {
+        match self {
+            Kind::Compile => PathBuf::from("compile"),
+            Kind::Execute => Path::new("execute").join("torture"),
+        }
+    }
+}
+
+/// Amount of test cases exported, and of passing test cases which were not
+#[derive(Debug, Default)]
+pub struct Exported {
+    pub exported: usize,
+    /// Test cases already in the gccrs test-suite
+    pub existing: usize,
+    /// Test cases which cannot be expressed as a single DejaGnu test case, for example
+    /// because they need auxiliary crates or rustc-specific flags
+    pub unsupported: usize,
+}
+
+/// Find the rustc test case a test result refers to. Test case names contain the path of
+/// the copy of the test case in the adaptor's output directory, which mirrors the rust
+/// repository, and optionally a revision: ``Compile full success `out/tests/ui/foo.rs#a` ``
+fn rustc_test(name: &str, layout: &RustLayout) -> Option<(PathBuf, Option<String>)> {
+    let start = name.find('`')?;
+    let end = name.rfind('`').filter(|&end| end > start)?;
+    let (path, revision) = match name[start + 1..end].split_once('#') {
+        Some((path, revision)) => (path, Some(revision.to_string())),
+        None => (&name[start + 1..end], None),
+    };
+
+    let components: Vec<Component> = Path::new(path).components().collect();
+    let tests = layout.tests();
+
+    (0..components.len())
+        .map(|idx| {
+            layout
+                .root()
+                .join(components[idx..].iter().collect::<PathBuf>())
+        })
+        .find(|candidate| candidate.starts_with(&tests) && candidate.is_file())
+        .map(|path| (path, revision))
+}
+
+/// Name of the converted test case: its path in the rustc test-suite, flattened since
+/// the gccrs test-suite does not have sub-directories, and its revision
+fn test_name(file: &Path, revision: Option<&str>, layout: &RustLayout) -> String {
+    let relative = file
+        .strip_prefix(layout.ui_tests())
+        .or_else(|_| file.strip_prefix(layout.tests()))
+        .unwrap_or(file)
+        .with_extension("");
+    let mut name: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    name.extend(revision.map(String::from));
+
+    format!("{}.rs", name.join("-"))
+}
+
+/// Make a rustc error message usable as the comment of a DejaGnu directive, replacing
+/// the characters which have a meaning in Tcl
+fn sanitize(message: &str) -> String {
+    message
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '[' | ']' | '{' | '}' | '$' | '`' => '.',
+            c => c,
+        })
+        .collect()
+}
+
+/// `dg-output` directives matching an expected standard output, one per line. Patterns
+/// are braced, so that the backslashes of escaped characters are kept as-is
+fn output_directives(stdout: &str) -> impl Iterator<Item = String> + '_ {
+    stdout
+        .lines()
+        .map(|line| format!("// {{ dg-output {{{}\\r*\\n}} }}", regex::escape(line)))
+}
+
+/// Translate the rustc flags of a test case into `gccrs` options. Only `--cfg` has an
+/// equivalent, other flags make the test case unsupported
+fn translate_flags(flags: &[String]) -> Option<Vec<String>> {
+    let mut options = Vec::new();
+    let mut flags = flags.iter();
+
+    while let Some(flag) = flags.next() {
+        let cfg = match flag.strip_prefix("--cfg") {
+            Some("") => flags.next()?.as_str(),
+            Some(cfg) => cfg.strip_prefix('=')?,
+            None => return None,
+        };
+
+        options.push(format!("-frust-cfg={cfg}"));
+    }
+
+    Some(options)
+}
+
+/// Rewrite a rustc test case into a DejaGnu test case. Headers are replaced with options
+/// and annotations with `dg-error` directives placed right after the line they expect an
+/// error on, since DejaGnu only supports a single directive per line
+fn convert(content: &str, headers: &Headers, flags: Vec<String>, stdout: Option<&str>) -> String {
+    let mut options = vec![String::from("-w")];
+    options.extend(flags);
+    options.extend(
+        headers
+            .edition
+            .map(|edition| format!("-frust-edition={}", edition.to_str())),
+    );
+    options.extend(
+        headers
+            .revision
+            .as_ref()
+            .map(|revision| format!("-frust-cfg={revision}")),
+    );
+
+    let mut converted = vec![format!(
+        "// {{ dg-additional-options \"{}\" }}",
+        options.join(" ")
+    )];
+    let header_count = compiletest::header_count(content);
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_number = idx + 1;
+
+        if idx < header_count && compiletest::is_header(line) {
+            continue;
+        }
+
+        match compiletest::annotation_start(line) {
+            // Lines which only contained annotations are removed
+            Some(start) if line[..start].trim().is_empty() => {}
+            Some(start) => converted.push(line[..start].trim_end().to_string()),
+            None => converted.push(line.to_string()),
+        }
+
+        let errors = headers
+            .annotations
+            .iter()
+            .filter(|annotation| annotation.line == line_number);
+        let indent = &line[..line.len() - line.trim_start().len()];
+
+        for (offset, annotation) in errors.enumerate() {
+            converted.push(format!(
+                "{indent}// {{ dg-error \"\" \"{}\" {{ target *-*-* }} .-{} }}",
+                sanitize(&annotation.message),
+                offset + 1
+            ));
+        }
+    }
+
+    converted.extend(stdout.into_iter().flat_map(output_directives));
+
+    converted.join("\n") + "\n"
+}
+
+/// Convert a single rustc test case, returning its converted content if it can be
+/// expressed as a DejaGnu test case
+fn convert_test(file: &Path, revision: Option<&str>, kind: Kind) -> Result<Option<String>, Error> {
+    let content = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+    let Some(headers) = Headers::parse_revisions(&content)
+        .into_iter()
+        .find(|headers| headers.revision.as_deref() == revision)
+    else {
+        return Ok(None);
+    };
+
+    // Auxiliary crates, most rustc flags and other files cannot be expressed in a DejaGnu
+    // test case compiled on its own
+    let Some(flags) = translate_flags(&headers.compile_flags) else {
+        return Ok(None);
+    };
+    if !headers.aux_builds.is_empty() || modules::declares_modules(&content) {
+        return Ok(None);
+    }
+
+    let stdout = match kind {
+        Kind::Compile => None,
+        // DejaGnu expects executed test cases to exit successfully
+        Kind::Execute if headers.mode != Some(Mode::RunPass) => return Ok(None),
+        Kind::Execute => headers.expected_stdout(file)?,
+    };
+
+    Ok(Some(convert(&content, &headers, flags, stdout.as_deref())))
+}
+
+/// Convert the rustc test cases which passed in `report` and are not part of the gccrs
+/// test-suite yet, and write them to `output_dir`
+pub fn export(
+    report: &Report,
+    layout: &RustLayout,
+    gccrs_path: &Path,
+    output_dir: &Path,
+) -> Result<Exported, Error> {
+    // The same test case can pass multiple passes, in which case it is only exported once
+    let mut tests: BTreeMap<_, BTreeSet<Kind>> = BTreeMap::new();
+    for result in report
+        .tests
+        .iter()
+        .filter(|result| result.outcome == Outcome::Pass)
+    {
+        let Some(kind) = Kind::from_pass(&result.pass) else {
+            continue;
+        };
+        let Some(test) = rustc_test(&result.name, layout) else {
+            continue;
+        };
+
+        tests.entry(test).or_default().insert(kind);
+    }
+
+    let testsuite = gccrs_path.join(TESTSUITE);
+    let mut exported = Exported::default();
+
+    for ((file, revision), kinds) in tests {
+        let name = test_name(&file, revision.as_deref(), layout);
+
+        if kinds
+            .iter()
+            .any(|kind| testsuite.join(kind.dir()).join(&name).exists())
+        {
+            exported.existing += 1;
+            continue;
+        }
+
+        // Prefer `execute` test cases, and fall back to `compile` ones if the test case
+        // cannot be executed by DejaGnu
+        let mut converted = None;
+        for kind in kinds.into_iter().rev() {
+            if let Some(content) = convert_test(&file, revision.as_deref(), kind)? {
+                converted = Some((kind, content));
+                break;
+            }
+        }
+
+        let Some((kind, converted)) = converted else {
+            exported.unsupported += 1;
+            continue;
+        };
+
+        let dir = output_dir.join(kind.dir());
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(name), converted)?;
+
+        exported.exported += 1;
+    }
+
+    Ok(exported)
+}
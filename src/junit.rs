@@ -0,0 +1,175 @@
+//! JUnit XML output of a test run, which can be consumed by most CI test viewers. Each
+//! pass becomes a `<testsuite>` and each test case a `<testcase>`. Failures are reported
+//! as `<failure>`, timeouts as `<error>`, and skipped test cases as well as expected
+//! failures as `<skipped>`. Unexpected passes are reported as successful test cases.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::runner::{Outcome, TestResult};
+
+/// Escape a string so that it can be used in XML text and attribute values. Characters
+/// which are not allowed in XML 1.0 documents, such as the ANSI escape sequences used
+/// by compilers to color their diagnostics, are replaced
+fn escape(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut acc, c| {
+            match c {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                '\'' => acc.push_str("&apos;"),
+                '\t' | '\n' | '\r' => acc.push(c),
+                c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {
+                    acc.push(char::REPLACEMENT_CHARACTER);
+                }
+                c => acc.push(c),
+            }
+
+            acc
+        })
+}
+
+/// Amount of test cases of each kind in a `<testsuite>`
+#[derive(Default)]
+struct Counts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    time: f64,
+}
+
+impl Counts {
+    fn new<'a>(results: impl Iterator<Item = &'a TestResult>) -> Counts {
+        results.fold(Counts::default(), |mut counts, result| {
+            counts.tests += 1;
+            counts.time += result.duration;
+            match result.outcome {
+                Outcome::Pass | Outcome::XPass => {}
+                Outcome::Fail => counts.failures += 1,
+                Outcome::Timeout => counts.errors += 1,
+                Outcome::Skip | Outcome::XFail => counts.skipped += 1,
+            }
+
+            counts
+        })
+    }
+
+    fn attributes(&self) -> String {
+        format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+            self.tests, self.failures, self.errors, self.skipped, self.time
+        )
+    }
+}
+
+fn write_test_case(xml: &mut String, result: &TestResult) -> std::fmt::Result {
+    writeln!(
+        xml,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+        escape(&result.name),
+        escape(&result.pass),
+        result.duration
+    )?;
+
+    match result.outcome {
+        Outcome::Pass | Outcome::XPass => {}
+        Outcome::XFail => writeln!(
+            xml,
+            "      <skipped type=\"xfail\" message=\"expected failure: {}\"/>",
+            escape(result.reason.as_deref().unwrap_or_default())
+        )?,
+        Outcome::Fail => {
+            let (kind, message) = result.failure();
+            writeln!(
+                xml,
+                "      <failure type=\"{}\" message=\"{}\"/>",
+                kind,
+                escape(&message)
+            )?;
+        }
+        Outcome::Timeout => writeln!(
+            xml,
+            "      <error type=\"timeout\" message=\"test case timed out\"/>"
+        )?,
+        Outcome::Skip => writeln!(
+            xml,
+            "      <skipped message=\"{}\"/>",
+            escape(result.reason.as_deref().unwrap_or_default())
+        )?,
+    }
+
+    // JUnit only allows a single `<system-out>`, which also holds the XPASS note
+    let mut stdout = String::new();
+    if result.outcome == Outcome::XPass {
+        stdout = format!(
+            "XPASS: test case is expected to fail ({})\n",
+            result.reason.as_deref().unwrap_or_default()
+        );
+    }
+    stdout.push_str(&result.stdout);
+
+    if !stdout.is_empty() {
+        writeln!(xml, "      <system-out>{}</system-out>", escape(&stdout))?;
+    }
+
+    if !result.stderr.is_empty() {
+        writeln!(
+            xml,
+            "      <system-err>{}</system-err>",
+            escape(&result.stderr)
+        )?;
+    }
+
+    writeln!(xml, "    </testcase>")
+}
+
+/// Create the JUnit XML document for a test run. Test suites appear in the order in which
+/// their passes were ran
+fn to_xml(results: &[TestResult]) -> Result<String, std::fmt::Error> {
+    let passes = results.iter().fold(Vec::new(), |mut acc, result| {
+        if !acc.contains(&result.pass.as_str()) {
+            acc.push(result.pass.as_str());
+        }
+        acc
+    });
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+    writeln!(
+        xml,
+        "<testsuites name=\"gccrs\" {}>",
+        Counts::new(results.iter()).attributes()
+    )?;
+
+    for pass in passes {
+        let suite = || results.iter().filter(move |result| result.pass == pass);
+
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" {}>",
+            escape(pass),
+            Counts::new(suite()).attributes()
+        )?;
+
+        suite().try_for_each(|result| write_test_case(&mut xml, result))?;
+
+        writeln!(xml, "  </testsuite>")?;
+    }
+
+    writeln!(xml, "</testsuites>")?;
+
+    Ok(xml)
+}
+
+/// Write the results of a test run to a JUnit XML file, overwriting it if it already exists
+pub fn write(results: &[TestResult], path: &Path) -> Result<(), Error> {
+    // Formatting into a `String` cannot fail
+    let xml = to_xml(results).expect("formatting JUnit XML into a string failed");
+
+    Ok(fs::write(path, xml)?)
+}
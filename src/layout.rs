@@ -0,0 +1,106 @@
+//! The layout of the rust repository changed across versions: its test-suite moved from
+//! `src/test` to `tests` in Rust 1.74, and its libraries from `src/lib*` to `library/` in
+//! Rust 1.47. The layout of a checkout is probed once, and passes resolve the paths they
+//! need through it so that they work with both old tags and the current master branch.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+/// Release number of the compiler in `src/bootstrap/channel.rs`, in versions which do not
+/// have a `src/version` file
+const CFG_RELEASE_NUM: &str = r#"CFG_RELEASE_NUM: &str = "([^"]+)""#;
+
+/// Layout of a rust repository checkout
+#[derive(Debug)]
+pub struct RustLayout {
+    root: PathBuf,
+    /// Version of the checked out compiler, if it could be found
+    version: Option<String>,
+    /// Directory of the test-suite, relative to the root of the repository
+    tests: PathBuf,
+    /// Directory of the core library, relative to the root of the repository
+    core: PathBuf,
+    /// Crate root of the core library, relative to the root of the repository
+    core_root: PathBuf,
+}
+
+/// Find the version of the compiler checked out in a rust repository
+fn probe_version(root: &Path) -> Option<String> {
+    if let Ok(version) = fs::read_to_string(root.join("src").join("version")) {
+        return Some(version.trim().to_string());
+    }
+
+    let channel = fs::read_to_string(root.join("src").join("bootstrap").join("channel.rs")).ok()?;
+    let release = Regex::new(CFG_RELEASE_NUM).expect("invalid release number regex");
+
+    release
+        .captures(&channel)
+        .map(|captures| captures[1].to_string())
+}
+
+impl RustLayout {
+    /// Probe the layout of the rust repository located at `root`. Directories which cannot
+    /// be found default to their current location
+    pub fn probe(root: &Path) -> RustLayout {
+        let legacy_tests = Path::new("src").join("test");
+        let tests = if !root.join("tests").is_dir() && root.join(&legacy_tests).is_dir() {
+            legacy_tests
+        } else {
+            PathBuf::from("tests")
+        };
+
+        let legacy_core = Path::new("src").join("libcore");
+        let core = if !root.join("library").is_dir() && root.join(&legacy_core).is_dir() {
+            legacy_core
+        } else {
+            Path::new("library").join("core")
+        };
+
+        // The core library only has a `src` directory since it moved to `library/`
+        let core_root = [core.join("src").join("lib.rs"), core.join("lib.rs")]
+            .into_iter()
+            .find(|path| root.join(path).is_file())
+            .unwrap_or_else(|| core.join("src").join("lib.rs"));
+
+        RustLayout {
+            root: root.to_owned(),
+            version: probe_version(root),
+            tests,
+            core,
+            core_root,
+        }
+    }
+
+    /// Root of the rust repository
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Version of the checked out compiler, such as `1.49.0`
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Directory containing the whole rustc test-suite
+    pub fn tests(&self) -> PathBuf {
+        self.root.join(&self.tests)
+    }
+
+    /// Directory containing the `ui` test cases of the rustc test-suite
+    pub fn ui_tests(&self) -> PathBuf {
+        self.tests().join("ui")
+    }
+
+    /// Directory of the core library
+    pub fn core(&self) -> PathBuf {
+        self.root.join(&self.core)
+    }
+
+    /// Crate root of the core library. The other files of the core library are modules of
+    /// this crate, and are compiled through it
+    pub fn core_root(&self) -> PathBuf {
+        self.root.join(&self.core_root)
+    }
+}
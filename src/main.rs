@@ -1,24 +1,43 @@
 mod args;
+mod auxiliary;
+mod compare;
 mod compiler;
+mod compiletest;
+mod dejagnu;
+mod diagnostics;
 mod error;
+mod export;
+mod junit;
+mod layout;
 mod log;
+mod modules;
+mod oracle;
 mod passes;
+mod report;
+mod runner;
 mod steps;
+mod xfail;
 
+use std::collections::HashSet;
 use std::ffi::OsStr;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::fs;
 use std::path::{Path, PathBuf};
 
-use args::Args;
+use args::{Args, Cli, Command};
+use compare::Comparison;
 use error::Error;
-use passes::{Pass, PassKind};
+use layout::RustLayout;
+use oracle::Oracle;
+use passes::{Pass, PassKind, TestCase, TestSuite};
+use report::{Report, RunInfo, Versions};
+use runner::TestResult;
 
 use clap::Parser;
 use rayon::prelude::*;
 use steps::CompileStep;
 use walkdir::WalkDir;
 use which::which;
+use xfail::XFailList;
 
 fn maybe_create_output_dir(path: &Path) -> Result<(), Error> {
     if !path.exists() {
@@ -27,31 +46,69 @@ fn maybe_create_output_dir(path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-/// Fetch a list of all the rust files (*.rs) contained in a directory and return their path
+/// Fetch a list of all the rust files (*.rs) contained in a directory and return their path.
+/// Files are always returned in the same order, regardless of the underlying filesystem
 #[must_use]
 pub fn fetch_rust_files(from: &Path) -> Vec<walkdir::DirEntry> {
     WalkDir::new(from)
+        .sort_by_file_name()
         .into_iter()
         .filter_map(Result::ok)
         .filter(|entry| entry.path().extension() == Some(OsStr::new("rs")))
         .collect()
 }
 
-/// Copies `*.rs` files from the path `from` to the path `to`, while stripping the prefix
-/// `prefix_to_strip` from the path.
-///
-/// # Errors
-///
-/// This functions returns an error if either
-/// - it fails to strip the prefix
-/// - it fails to create the new directory
-/// - it fails to copy the file to the new location
-pub fn copy_rs_files(
+/// Split the rust files contained in a directory between test files, which are only kept
+/// if they are selected by the `--include`, `--exclude` and `--filter` arguments, and the
+/// files needed by test files: auxiliary crates, out-of-line modules and the dependencies
+/// of gccrs `link` test cases. Test files are selected based on their path relative to
+/// `root`, which should be the root of the repository they come from
+fn split_test_files(
+    args: &Args,
     from: &Path,
+    root: &Path,
+) -> (Vec<walkdir::DirEntry>, Vec<walkdir::DirEntry>) {
+    let entries = fetch_rust_files(from);
+    let paths: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| entry.path().to_owned())
+        .collect();
+    let submodules = modules::submodules(&paths);
+    let link_dependencies = auxiliary::link_dependencies(&paths);
+
+    let (test_files, needed_files): (Vec<_>, Vec<_>) = entries.into_iter().partition(|entry| {
+        !auxiliary::is_auxiliary(entry.path())
+            && !submodules.contains(entry.path())
+            && !link_dependencies.contains(entry.path())
+    });
+
+    let test_files = test_files
+        .into_iter()
+        .filter(|entry| {
+            entry
+                .path()
+                .strip_prefix(root)
+                .is_ok_and(|relative| args.is_selected(relative))
+        })
+        .collect();
+
+    (test_files, needed_files)
+}
+
+/// Fetch a list of all the test files contained in a directory, only keeping the crate
+/// roots selected by the `--include`, `--exclude` and `--filter` arguments. See
+/// [`split_test_files`]
+#[must_use]
+pub fn fetch_test_files(args: &Args, from: &Path, root: &Path) -> Vec<walkdir::DirEntry> {
+    split_test_files(args, from, root).0
+}
+
+fn copy_entries(
+    entries: Vec<walkdir::DirEntry>,
     to: &Path,
     prefix_to_strip: &Path,
 ) -> Result<Vec<PathBuf>, Error> {
-    fetch_rust_files(from)
+    entries
         .into_par_iter()
         .map(move |entry| {
             let old_path = entry.path();
@@ -69,6 +126,86 @@ pub fn copy_rs_files(
         .collect()
 }
 
+/// Copies `*.rs` files from the path `from` to the path `to`, while stripping the prefix
+/// `prefix_to_strip` from the path.
+///
+/// # Errors
+///
+/// This functions returns an error if either
+/// - it fails to strip the prefix
+/// - it fails to create the new directory
+/// - it fails to copy the file to the new location
+pub fn copy_rs_files(
+    from: &Path,
+    to: &Path,
+    prefix_to_strip: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    copy_entries(fetch_rust_files(from), to, prefix_to_strip)
+}
+
+/// Copies the test files selected by the arguments from the path `from` to the path `to`,
+/// while stripping the prefix `prefix_to_strip` from the path. See [`split_test_files`] and
+/// [`copy_rs_files`]. Auxiliary crates and out-of-line modules are always copied, as any
+/// selected test file might need them, but they are not returned as test files.
+///
+/// # Errors
+///
+/// This functions returns an error in the same cases as [`copy_rs_files`]
+pub fn copy_test_files(
+    args: &Args,
+    from: &Path,
+    to: &Path,
+    prefix_to_strip: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let (test_files, needed_files) = split_test_files(args, from, prefix_to_strip);
+
+    copy_entries(needed_files, to, prefix_to_strip)?;
+    copy_entries(test_files, to, prefix_to_strip)
+}
+
+/// Copies the files ending with `suffix` from the path `from` to the path `to`, while
+/// stripping the prefix `prefix_to_strip` from the path. This is used to copy the expected
+/// outputs of test cases, such as `.stderr` files, alongside them: only the outputs of the
+/// test cases in `tests`, as returned by [`copy_test_files`], are copied
+///
+/// # Errors
+///
+/// This functions returns an error in the same cases as [`copy_rs_files`]
+pub fn copy_expected_outputs(
+    tests: &[PathBuf],
+    from: &Path,
+    to: &Path,
+    prefix_to_strip: &Path,
+    suffix: &str,
+) -> Result<(), Error> {
+    // Expected outputs are named after their test case, optionally followed by a revision:
+    // `foo.stderr` or `foo.rev.stderr` for `foo.rs`
+    let selected: HashSet<PathBuf> = tests
+        .iter()
+        .filter_map(|test| test.strip_prefix(to).ok())
+        .map(|test| prefix_to_strip.join(test).with_extension(""))
+        .collect();
+
+    let entries = WalkDir::new(from)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            let Some(test) = name.strip_suffix(suffix) else {
+                return false;
+            };
+            let without_revision = test.rsplit_once('.').map(|(test, _)| test);
+
+            std::iter::once(test)
+                .chain(without_revision)
+                .any(|test| selected.contains(&entry.path().with_file_name(test)))
+        })
+        .collect();
+
+    copy_entries(entries, to, prefix_to_strip).map(|_| ())
+}
+
 fn pass_dispatch(pass: PassKind) -> Vec<Box<dyn Pass>> {
     match pass {
         PassKind::GccrsParsing => vec![Box::new(passes::GccrsParsing)],
@@ -76,6 +213,9 @@ fn pass_dispatch(pass: PassKind) -> Vec<Box<dyn Pass>> {
         PassKind::GccrsRustcSucess => vec![Box::new(passes::GccrsRustcSuccesses::Full)],
         PassKind::GccrsRustcSucessNoStd => vec![Box::new(passes::GccrsRustcSuccesses::NoStd)],
         PassKind::GccrsRustcSucessNoCore => vec![Box::new(passes::GccrsRustcSuccesses::NoCore)],
+        PassKind::GccrsRustcErrors => vec![Box::new(passes::GccrsRustcErrors)],
+        PassKind::GccrsRustcRun => vec![Box::new(passes::GccrsRustcRun)],
+        PassKind::GccrsRustcStderr => vec![Box::new(passes::GccrsRustcStderr)],
         PassKind::Blake3 => passes::Blake3::variants()
             .into_iter()
             .map(|blake_variant| Box::new(blake_variant) as Box<dyn Pass>)
@@ -94,21 +234,30 @@ fn pass_dispatch(pass: PassKind) -> Vec<Box<dyn Pass>> {
         .into_iter()
         .flatten()
         .collect(),
-        PassKind::AstExport => vec![
-            Box::new(passes::AstExport::Compile),
-            Box::new(passes::AstExport::Run),
-        ],
+        PassKind::AstExport => passes::AstExport::phases()
+            .into_iter()
+            .map(|phase| Box::new(phase) as Box<dyn Pass>)
+            .collect(),
+        PassKind::All => PassKind::variants()
+            .into_iter()
+            .flat_map(pass_dispatch)
+            .collect(),
     }
 }
 
-fn apply_pass(pass: &dyn Pass, args: &Args, files: &[PathBuf]) -> Result<String, Error> {
+/// Adapt all the files fetched by a pass. Test cases are returned in the same order as
+/// `files`, regardless of the amount of threads used
+fn apply_pass(
+    pass: &dyn Pass,
+    args: &Args,
+    oracle: &Oracle,
+    files: &[PathBuf],
+) -> Result<Vec<TestCase>, Error> {
     files
         .into_par_iter()
-        .map(|file| pass.adapt(args, file))
-        .try_fold(String::new, |acc, test_case: Result<_, Error>| {
-            Ok(format!("{}{}", acc, test_case?))
-        })
-        .collect()
+        .map(|file| pass.adapt(args, oracle, file))
+        .collect::<Result<Vec<_>, Error>>()
+        .map(|test_cases| test_cases.into_iter().flatten().collect())
 }
 
 fn warn_on_file_not_found(name: &str, path: &Path) {
@@ -123,35 +272,26 @@ fn warn_on_file_not_found(name: &str, path: &Path) {
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+/// Run all the passes associated with a [`PassKind`] and write the resulting test-suite
+/// to the YAML file given in `args`. If requested, the test-suite is then run and its
+/// results returned
+fn run_pass_kind(
+    pass_kind: PassKind,
+    args: &Args,
+    layout: &RustLayout,
+    oracle: &Oracle,
+) -> Result<Vec<TestResult>, Error> {
     maybe_create_output_dir(&args.output_dir)?;
-    if !args.rust_path.exists() {
-        return Err(Error::NoRust(args.rust_path).into());
-    }
-    if !args.gccrs_path.exists() {
-        return Err(Error::NoGccrs(args.gccrs_path).into());
-    }
-
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(args.jobs)
-        .build_global()?;
 
-    warn_on_file_not_found("rustc", &args.rustc);
-    warn_on_file_not_found("gccrs", &args.gccrs);
-
-    let ftf_header = String::from("tests:\n");
-
-    let pass_kind = args.pass;
     let passes = pass_dispatch(pass_kind);
     log!("running pass `{}`...", pass_kind);
 
-    let test_suites: Result<Vec<String>, Error> = passes
+    let test_suites: Result<Vec<Vec<TestCase>>, Error> = passes
         .iter()
         .map(|pass| {
             log!("fetching test files for `{}`...", pass_kind);
 
-            let files = pass.fetch(&args)?;
+            let files = pass.fetch(args, layout)?;
 
             log!(
                 "generating test cases for `{}`... this might take a while",
@@ -159,7 +299,7 @@ fn main() -> anyhow::Result<()> {
             );
 
             // This is ugly!
-            let test_suite = apply_pass(&**pass, &args, &files)?;
+            let test_suite = apply_pass(&**pass, args, oracle, &files)?;
 
             log!("`{}` pass complete!", pass_kind);
 
@@ -167,17 +307,208 @@ fn main() -> anyhow::Result<()> {
         })
         .collect();
 
-    let mut yml = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(&args.yaml)?;
+    let xfails = XFailList::load(args.xfail_dir.as_deref(), pass_kind)?;
+    if xfails.len() > 0 {
+        log!(
+            "marking test cases matching {} patterns as expected failures for `{}`",
+            xfails.len(),
+            pass_kind
+        );
+    }
 
-    yml.write_all(ftf_header.as_bytes())?;
+    let test_suite = TestSuite::new(
+        pass_kind,
+        test_suites?
+            .into_iter()
+            .flatten()
+            .map(|test_case| xfails.apply(test_case)),
+    );
 
-    test_suites?
-        .iter()
-        .try_for_each(|suite| yml.write_all(suite.as_bytes()))?;
+    test_suite.write(&args.yaml)?;
+
+    if !args.run {
+        return Ok(vec![]);
+    }
+
+    log!("running test cases for `{}`...", pass_kind);
+
+    Ok(run_test_suite(&test_suite))
+}
+
+/// Run all the test cases of a test-suite and print a summary of the results
+fn run_test_suite(test_suite: &TestSuite) -> Vec<TestResult> {
+    let results = runner::run(test_suite);
+
+    // The summary is the only thing printed on stdout, so that it can easily be scraped
+    let summary = runner::Summary::new(&results);
+    println!("{summary}");
+
+    if let Some(score) = summary.score {
+        log!("average diagnostics score: {:.3}", score);
+    }
+
+    results
+}
+
+/// Write the results of a test run to the report files requested on the command line
+fn write_results(
+    results: Vec<TestResult>,
+    report: Option<&Path>,
+    run_info: RunInfo,
+    junit: Option<&Path>,
+    rustc: Option<&Path>,
+    gccrs: Option<&Path>,
+) -> Result<(), Error> {
+    if let Some(junit) = junit {
+        junit::write(&results, junit)?;
+    }
+
+    if let Some(report) = report {
+        Report::new(run_info, Versions::probe(rustc, gccrs), results).write(report)?;
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let args = match (cli.command, cli.args) {
+        (
+            Some(Command::Run {
+                yaml,
+                jobs,
+                report,
+                junit,
+                rustc,
+                gccrs,
+                report_name,
+                report_commit,
+                report_date,
+            }),
+            _,
+        ) => {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build_global()?;
+
+            log!("running test-suite `{}`...", yaml.display());
+            let results = run_test_suite(&TestSuite::read(&yaml)?);
+
+            write_results(
+                results,
+                report.as_deref(),
+                RunInfo {
+                    name: report_name,
+                    commit: report_commit,
+                    date: report_date,
+                },
+                junit.as_deref(),
+                rustc.as_deref(),
+                gccrs.as_deref(),
+            )?;
+
+            return Ok(());
+        }
+        (Some(Command::Compare { old, new }), _) => {
+            let old = Report::read(&old)?;
+            let new = Report::read(&new)?;
+            let comparison = Comparison::new(&old, &new);
+
+            print!("{comparison}");
+
+            if comparison.regressions > 0 {
+                return Err(Error::Regressions(comparison.regressions).into());
+            }
+
+            return Ok(());
+        }
+        (
+            Some(Command::Export {
+                report,
+                rust_path,
+                gccrs_path,
+                output_dir,
+            }),
+            _,
+        ) => {
+            let report = Report::read(&report)?;
+            let layout = RustLayout::probe(&rust_path);
+
+            log!(
+                "exporting rustc test cases to `{}`...",
+                output_dir.display()
+            );
+            let exported = export::export(&report, &layout, &gccrs_path, &output_dir)?;
+
+            log!(
+                "exported {} test cases, skipped {} already in the gccrs test-suite and {} unsupported",
+                exported.exported,
+                exported.existing,
+                exported.unsupported
+            );
+
+            return Ok(());
+        }
+        (None, Some(args)) => args,
+        // clap makes sure that the generation arguments are present when no subcommand is given
+        (None, None) => unreachable!(),
+    };
+
+    maybe_create_output_dir(&args.output_dir)?;
+    if !args.rust_path.exists() {
+        return Err(Error::NoRust(args.rust_path).into());
+    }
+    if !args.gccrs_path.exists() {
+        return Err(Error::NoGccrs(args.gccrs_path).into());
+    }
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs)
+        .build_global()?;
+
+    warn_on_file_not_found("rustc", &args.rustc);
+    warn_on_file_not_found("gccrs", &args.gccrs);
+
+    // The layout of the rust repository is probed once, and shared between all passes
+    let layout = RustLayout::probe(&args.rust_path);
+    log!(
+        "using rust repository `{}` (version {}), with tests in `{}`",
+        layout.root().display(),
+        layout.version().unwrap_or("unknown"),
+        layout.tests().display()
+    );
+
+    // The oracle is shared between all passes, so that `rustc` only runs once per file
+    let oracle = Oracle::new(&args.rustc, args.oracle_cache.clone());
+    let pass_kinds = PassKind::expand(&args.pass);
+    let multiple_passes = pass_kinds.len() > 1;
+
+    let results = pass_kinds
+        .into_iter()
+        .map(|pass_kind| {
+            let results = run_pass_kind(
+                pass_kind,
+                &args.for_pass(pass_kind, multiple_passes),
+                &layout,
+                &oracle,
+            )?;
+
+            // Save the oracle's results after each pass, so that they are kept even if a later pass fails
+            oracle.save()?;
+
+            Ok(results)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    write_results(
+        results.into_iter().flatten().collect(),
+        args.report.as_deref(),
+        args.run_info(),
+        args.junit.as_deref(),
+        Some(&args.rustc),
+        Some(&args.gccrs),
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,235 @@
+//! Discovery of out-of-line modules, declared with `mod foo;` and optionally a
+//! `#[path = "..."]` attribute. Files which are declared as a module of another file are
+//! not crate roots: they can only be compiled through the file declaring them, and are
+//! thus not test cases on their own.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+use regex::Regex;
+
+/// Out-of-line module declaration, such as `pub(crate) mod foo;`
+const MOD_DECLARATION: &str =
+    r"^(?:pub(?:\s*\([^)]*\))?\s+)?mod\s+(?:r#)?([A-Za-z_][A-Za-z0-9_]*)\s*;";
+
+/// Attribute overriding the path of a module, such as `#[path = "foo/bar.rs"]`
+const PATH_ATTRIBUTE: &str = r#"#\[\s*path\s*=\s*"([^"]+)"\s*\]"#;
+
+/// Module declaration found in a file
+#[derive(Clone)]
+struct Declaration {
+    name: String,
+    path: Option<String>,
+}
+
+/// Find all the out-of-line module declarations of a file. Only unindented declarations
+/// are considered, as the path of modules declared inside inline modules depends on the
+/// inline module
+fn declarations(
+    content: &str,
+    mod_declaration: &Regex,
+    path_attribute: &Regex,
+) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut path = None;
+
+    for line in content.lines() {
+        if let Some(captures) = mod_declaration.captures(line) {
+            declarations.push(Declaration {
+                name: captures[1].to_string(),
+                path: path.take(),
+            });
+            continue;
+        }
+
+        let line = line.trim();
+        if line.starts_with("#[") {
+            // Attributes apply to the next item, which might be a module declaration
+            if let Some(captures) = path_attribute.captures(line) {
+                path = Some(captures[1].to_string());
+            }
+        } else if !line.is_empty() && !line.starts_with("//") {
+            path = None;
+        }
+    }
+
+    declarations
+}
+
+/// Resolve `.` and `..` in a path without accessing the filesystem, so that paths coming
+/// from `#[path]` attributes can be compared with other paths
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .fold(PathBuf::new(), |mut normalized, component| {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir if normalized.file_name().is_some() => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+
+            normalized
+        })
+}
+
+/// Directory in which the modules declared by a file are looked for. Like in rustc, crate
+/// roots, `mod.rs` files and files loaded through a `#[path]` attribute own their
+/// directory, while the modules of other files live in a directory named after them
+fn module_dir(file: &Path, owns_dir: bool) -> PathBuf {
+    let dir = file.parent().unwrap_or(Path::new(""));
+
+    if owns_dir || file.file_name() == Some("mod.rs".as_ref()) {
+        dir.to_owned()
+    } else {
+        dir.join(file.file_stem().unwrap_or_default())
+    }
+}
+
+/// Find the file a module declaration of `file` refers to, among the files which `exists`.
+/// Returns whether the module file owns its directory along with its path
+fn resolve(
+    file: &Path,
+    owns_dir: bool,
+    declaration: &Declaration,
+    exists: &dyn Fn(&Path) -> bool,
+) -> Option<(PathBuf, bool)> {
+    if let Some(path) = &declaration.path {
+        // `#[path]` attributes are relative to the directory of the declaring file
+        let dir = file.parent().unwrap_or(Path::new(""));
+        return Some((normalize(&dir.join(path)), true)).filter(|(path, _)| exists(path));
+    }
+
+    let name = &declaration.name;
+    let dir = module_dir(file, owns_dir);
+
+    [
+        (dir.join(format!("{name}.rs")), false),
+        (dir.join(name).join("mod.rs"), true),
+    ]
+    .into_iter()
+    .find(|(candidate, _)| candidate != file && exists(candidate))
+}
+
+/// Follow the module declarations of the crate root `root`, and add all the module files
+/// of the crate to `modules`
+fn walk(
+    root: &Path,
+    declarations: &dyn Fn(&Path) -> Vec<Declaration>,
+    exists: &dyn Fn(&Path) -> bool,
+    modules: &mut HashSet<PathBuf>,
+) {
+    let mut stack = vec![(root.to_owned(), true)];
+
+    while let Some((file, owns_dir)) = stack.pop() {
+        for declaration in declarations(&file) {
+            let Some((module, owns_dir)) = resolve(&file, owns_dir, &declaration, exists) else {
+                continue;
+            };
+
+            if module != root && modules.insert(module.clone()) {
+                stack.push((module, owns_dir));
+            }
+        }
+    }
+}
+
+/// Read the module declarations of a file. Some test cases are not valid UTF-8, and
+/// unreadable files cannot declare modules
+fn read_declarations(
+    file: &Path,
+    mod_declaration: &Regex,
+    path_attribute: &Regex,
+) -> Vec<Declaration> {
+    let content = fs::read(file)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+
+    declarations(&content, mod_declaration, path_attribute)
+}
+
+/// Find all the module files of the crate whose root is `root`, in a stable order
+pub fn module_files(root: &Path) -> Vec<PathBuf> {
+    let mod_declaration = Regex::new(MOD_DECLARATION).expect("invalid module declaration regex");
+    let path_attribute = Regex::new(PATH_ATTRIBUTE).expect("invalid path attribute regex");
+
+    let declarations = |file: &Path| read_declarations(file, &mod_declaration, &path_attribute);
+    let mut modules = HashSet::new();
+
+    walk(
+        &normalize(root),
+        &declarations,
+        &Path::is_file,
+        &mut modules,
+    );
+
+    let mut modules: Vec<PathBuf> = modules.into_iter().collect();
+    modules.sort();
+
+    modules
+}
+
+/// Does a file declare out-of-line modules? Such files cannot be compiled on their own
+pub fn declares_modules(content: &str) -> bool {
+    let mod_declaration = Regex::new(MOD_DECLARATION).expect("invalid module declaration regex");
+
+    content.lines().any(|line| mod_declaration.is_match(line))
+}
+
+/// Find all the files among `files` which are declared as modules by another file of
+/// `files`. The remaining files are crate roots
+pub fn submodules(files: &[PathBuf]) -> HashSet<&Path> {
+    let mod_declaration = Regex::new(MOD_DECLARATION).expect("invalid module declaration regex");
+    let path_attribute = Regex::new(PATH_ATTRIBUTE).expect("invalid path attribute regex");
+
+    let normalized: Vec<PathBuf> = files.iter().map(|file| normalize(file)).collect();
+    let all_declarations: HashMap<&Path, Vec<Declaration>> = normalized
+        .par_iter()
+        .map(|file| {
+            let declarations = read_declarations(file, &mod_declaration, &path_attribute);
+            (file.as_path(), declarations)
+        })
+        .collect();
+
+    let declarations = |file: &Path| all_declarations.get(file).cloned().unwrap_or_default();
+    let exists = |file: &Path| all_declarations.contains_key(file);
+
+    // Whether a file is a crate root cannot be known in advance. Files which no other file
+    // could declare are crate roots, and the crates are walked from them first. Files
+    // which are not reached by these crates are crate roots as well
+    let maybe_modules: HashSet<PathBuf> = normalized
+        .iter()
+        .flat_map(|file| {
+            declarations(file).into_iter().flat_map(move |declaration| {
+                [true, false].into_iter().filter_map(move |owns_dir| {
+                    resolve(file, owns_dir, &declaration, &exists)
+                        .map(|(module, _)| module)
+                        .filter(|module| module != file)
+                })
+            })
+        })
+        .collect();
+
+    let (roots, others): (Vec<&PathBuf>, Vec<&PathBuf>) = normalized
+        .iter()
+        .partition(|file| !maybe_modules.contains(*file));
+
+    let mut modules = HashSet::new();
+    for root in roots {
+        walk(root, &declarations, &exists, &mut modules);
+    }
+    for root in others {
+        if !modules.contains(root) {
+            walk(root, &declarations, &exists, &mut modules);
+        }
+    }
+
+    files
+        .iter()
+        .zip(&normalized)
+        .filter(|(_, normalized)| modules.contains(*normalized))
+        .map(|(file, _)| file.as_path())
+        .collect()
+}
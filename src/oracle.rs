@@ -0,0 +1,159 @@
+//! `rustc` is used as an oracle by multiple passes, in order to know whether or not a test
+//! case is valid Rust code. Launching `rustc` on the whole rustc test-suite is extremely
+//! expensive, so the results are shared between all the passes run in the same invocation,
+//! and can be persisted on disk in order to be reused by later invocations.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use wait_timeout::ChildExt;
+
+use crate::compiler::{self, Compiler};
+use crate::error::Error;
+use crate::modules;
+use crate::{log, warn};
+
+/// Memoized `rustc` results, keyed by the content of the file, the version of `rustc`
+/// and the arguments given to the compiler
+#[derive(Default)]
+pub struct Oracle {
+    results: Mutex<HashMap<String, bool>>,
+    /// Version of the `rustc` compiler used as an oracle
+    version: String,
+    /// File in which results are persisted between invocations
+    cache: Option<PathBuf>,
+}
+
+impl Oracle {
+    /// Maximum amount of time given to `rustc` to check a single file
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Create a new oracle using `rustc`. If a `cache` file is given, results from previous
+    /// invocations are loaded from it
+    pub fn new(rustc: &Path, cache: Option<PathBuf>) -> Oracle {
+        let Some(version) = compiler::version(rustc) else {
+            if cache.is_some() {
+                warn!(
+                    "could not get the version of `{}`: oracle results will not be cached",
+                    rustc.display()
+                );
+            }
+
+            return Oracle::default();
+        };
+
+        let results = match &cache {
+            Some(path) if path.exists() => Oracle::load(path).unwrap_or_else(|e| {
+                warn!(
+                    "could not load oracle cache `{}`, ignoring it: {}",
+                    path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            _ => HashMap::new(),
+        };
+
+        if let Some(path) = &cache {
+            log!(
+                "loaded {} cached oracle results from `{}`",
+                results.len(),
+                path.display()
+            );
+        }
+
+        Oracle {
+            results: Mutex::new(results),
+            version,
+            cache,
+        }
+    }
+
+    fn load(path: &Path) -> Result<HashMap<String, bool>, Error> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Persist the results of the oracle, if a cache file was given
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.cache {
+            let json = BufWriter::new(File::create(path)?);
+            serde_json::to_writer(json, &*self.results.lock().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Compute the key associated with a compiler invocation on a specific file. The key
+    /// is a SHA-256 hash, which stays stable across invocations of the adaptor. On top of
+    /// the file itself, it covers every file the invocation reads: the out-of-line modules
+    /// of the file, and the crates given with `--extern`
+    fn key(&self, cmd: &Command, file: &Path) -> Result<String, Error> {
+        let mut hasher = Sha256::new();
+
+        hasher.update(self.version.as_bytes());
+        hasher.update([0]);
+        cmd.get_args().for_each(|arg| {
+            hasher.update(arg.as_encoded_bytes());
+            hasher.update([0]);
+        });
+        hasher.update(fs::read(file)?);
+
+        for module in modules::module_files(file) {
+            hasher.update([0]);
+            hasher.update(module.as_os_str().as_encoded_bytes());
+            hasher.update([0]);
+            hasher.update(fs::read(module)?);
+        }
+
+        let mut args = cmd.get_args();
+        while let Some(arg) = args.next() {
+            if arg != "--extern" {
+                continue;
+            }
+
+            let Some((_, library)) = args.next().and_then(|arg| arg.to_str()?.split_once('='))
+            else {
+                continue;
+            };
+
+            // Missing crates are hashed as empty files, and make the compiler fail anyway
+            hasher.update([0]);
+            hasher.update(fs::read(library).unwrap_or_default());
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Check if `compiler` accepts `file`. The compiler invocation should be fully built,
+    /// with the exception of the file to compile which will be added by the oracle. If the
+    /// same check has already been performed by another pass, the compiler is not launched
+    /// again
+    pub fn accepts(&self, mut compiler: Compiler, file: &Path) -> Result<bool, Error> {
+        let cmd = compiler.command();
+        let key = self.key(cmd, file)?;
+
+        if let Some(&is_valid) = self.results.lock().unwrap().get(&key) {
+            return Ok(is_valid);
+        }
+
+        let mut child = cmd.arg(file.as_os_str()).spawn()?;
+
+        // Timeouts are not cached, as they might only be caused by a machine under heavy load
+        let Some(status) = child.wait_timeout(Oracle::TIMEOUT)? else {
+            child.kill()?;
+            return Ok(false);
+        };
+
+        let is_valid = status.success();
+
+        self.results.lock().unwrap().insert(key, is_valid);
+
+        Ok(is_valid)
+    }
+}
@@ -1,6 +1,9 @@
 mod ast_export;
 mod blake3;
 mod gccrs_parsing;
+mod gccrs_rustc_errors;
+mod gccrs_rustc_run;
+mod gccrs_rustc_stderr;
 mod gccrs_rustc_successes;
 mod libcore;
 mod rustc_dejagnu;
@@ -8,31 +11,70 @@ mod rustc_dejagnu;
 pub use ast_export::AstExport;
 pub use blake3::Blake3;
 pub use gccrs_parsing::GccrsParsing;
+pub use gccrs_rustc_errors::GccrsRustcErrors;
+pub use gccrs_rustc_run::GccrsRustcRun;
+pub use gccrs_rustc_stderr::GccrsRustcStderr;
 pub use gccrs_rustc_successes::GccrsRustcSuccesses;
 pub use libcore::LibCore;
 pub use rustc_dejagnu::RustcDejagnu;
 
 use std::ffi::OsStr;
 use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
 use crate::compiler::Compiler;
+use crate::compiletest::Headers;
+use crate::diagnostics::{ExpectedErrors, ReferenceErrors};
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
 use crate::{args::Args, error::Error};
+use crate::{copy_expected_outputs, copy_test_files};
 
 /// Wrapper struct around an ftf test case. Ideally, this should be provided
 /// directly by the ftf crate
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+// Almost all test cases are actual tests, so boxing them would only add indirections
+#[allow(clippy::large_enum_variant)]
 pub enum TestCase {
     Test {
         name: String,
         binary: String,
         exit_code: u8,
         timeout: i32,
+        #[serde(default)]
         stderr: String,
+        #[serde(default)]
         stdout: String,
+        /// Regular expression which must match the standard output, on top of `stdout`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stdout_pattern: Option<String>,
+        /// Regular expression which must match the standard error, on top of `stderr`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stderr_pattern: Option<String>,
+        #[serde(default)]
         args: Vec<String>,
+        /// Reason for which the test case is expected to fail, if it is
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xfail: Option<String>,
+        /// Errors the compiler is expected to report, on top of the exit code
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_errors: Option<ExpectedErrors>,
+        /// Errors reported by rustc, to which the errors reported by the compiler are compared
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reference_errors: Option<ReferenceErrors>,
+        /// Reason for which the test case fails whatever its outcome, when the failure was
+        /// already found when generating it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        failure: Option<String>,
     },
-    Skip,
+    /// Test case which should not be ran, but which still appears in test reports
+    #[serde(skip)]
+    Skip { name: String, reason: String },
 }
 
 impl Default for TestCase {
@@ -45,7 +87,13 @@ impl Default for TestCase {
             timeout: Self::DEFAULT_TIMEOUT,
             stderr: String::new(),
             stdout: String::new(),
+            stdout_pattern: None,
+            stderr_pattern: None,
             args: vec![],
+            xfail: None,
+            expected_errors: None,
+            reference_errors: None,
+            failure: None,
         }
     }
 }
@@ -53,6 +101,19 @@ impl Default for TestCase {
 impl TestCase {
     const DEFAULT_TIMEOUT: i32 = 15; // default timeout is 15 minutes
 
+    pub fn skip<T: Display, U: Display>(name: T, reason: U) -> TestCase {
+        TestCase::Skip {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TestCase::Test { name, .. } | TestCase::Skip { name, .. } => name,
+        }
+    }
+
     pub fn from_compiler(mut compiler: Compiler) -> TestCase {
         let cmd = compiler.command();
         TestCase::default()
@@ -102,6 +163,81 @@ impl TestCase {
         args.fold(self, TestCase::with_arg)
     }
 
+    pub fn with_xfail<T: Display>(mut self, reason: T) -> TestCase {
+        if let TestCase::Test { ref mut xfail, .. } = self {
+            *xfail = Some(reason.to_string());
+        }
+
+        self
+    }
+
+    pub fn with_expected_errors(mut self, errors: ExpectedErrors) -> TestCase {
+        if let TestCase::Test {
+            ref mut expected_errors,
+            ..
+        } = self
+        {
+            *expected_errors = Some(errors);
+        }
+
+        self
+    }
+
+    pub fn with_reference_errors(mut self, errors: ReferenceErrors) -> TestCase {
+        if let TestCase::Test {
+            ref mut reference_errors,
+            ..
+        } = self
+        {
+            *reference_errors = Some(errors);
+        }
+
+        self
+    }
+
+    pub fn with_failure<T: Display>(mut self, reason: T) -> TestCase {
+        if let TestCase::Test {
+            ref mut failure, ..
+        } = self
+        {
+            *failure = Some(reason.to_string());
+        }
+
+        self
+    }
+
+    pub fn with_stdout<T: Display>(mut self, new_stdout: T) -> TestCase {
+        if let TestCase::Test { ref mut stdout, .. } = self {
+            *stdout = new_stdout.to_string();
+        }
+
+        self
+    }
+
+    pub fn with_stdout_pattern<T: Display>(mut self, pattern: T) -> TestCase {
+        if let TestCase::Test {
+            ref mut stdout_pattern,
+            ..
+        } = self
+        {
+            *stdout_pattern = Some(pattern.to_string());
+        }
+
+        self
+    }
+
+    pub fn with_stderr_pattern<T: Display>(mut self, pattern: T) -> TestCase {
+        if let TestCase::Test {
+            ref mut stderr_pattern,
+            ..
+        } = self
+        {
+            *stderr_pattern = Some(pattern.to_string());
+        }
+
+        self
+    }
+
     pub fn with_binary<T: Display>(mut self, new_binary: T) -> TestCase {
         if let TestCase::Test { ref mut binary, .. } = self {
             *binary = new_binary.to_string();
@@ -111,49 +247,114 @@ impl TestCase {
     }
 }
 
-impl Display for TestCase {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TestCase::Skip => Ok(()),
-            TestCase::Test {
-                name,
-                binary,
-                exit_code,
-                timeout,
-                stderr,
-                stdout,
-                args,
-            } => {
-                writeln!(f, "  - name: {name}")?;
-                writeln!(f, "    binary: {binary}")?;
-                writeln!(f, "    timeout: {timeout}")?;
-                writeln!(f, "    exit_code: {exit_code}")?;
-                writeln!(f, "    stdout: \"{stdout}\"")?;
-                writeln!(f, "    stderr: \"{stderr}\"")?;
-                writeln!(f, "    args:")?;
-
-                for arg in args {
-                    writeln!(f, "      - \"{arg}\"")?;
-                }
+/// Test case which was skipped when generating a test-suite
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Skipped {
+    pub name: String,
+    pub reason: String,
+}
 
-                Ok(())
-            }
+/// An ftf test-suite, as written to and read from the generated YAML files. Extra
+/// metadata is ignored by `ftf`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TestSuite {
+    /// Pass which generated the test-suite
+    #[serde(default)]
+    pub pass: String,
+    pub tests: Vec<TestCase>,
+    #[serde(default)]
+    pub skipped: Vec<Skipped>,
+}
+
+impl TestSuite {
+    /// Create a test-suite from a list of test cases. Skipped test cases are not ran as
+    /// part of the resulting test-suite, and are only kept for reporting purposes. Test
+    /// cases are sorted by name, so that generated test-suites can be diffed
+    pub fn new(pass: PassKind, test_cases: impl IntoIterator<Item = TestCase>) -> TestSuite {
+        let mut test_cases: Vec<TestCase> = test_cases.into_iter().collect();
+        test_cases.sort_by(|lhs, rhs| lhs.name().cmp(rhs.name()));
+
+        let (skipped, tests): (Vec<_>, Vec<_>) = test_cases
+            .into_iter()
+            .partition(|test_case| matches!(test_case, TestCase::Skip { .. }));
+
+        TestSuite {
+            pass: pass.to_string(),
+            tests,
+            skipped: skipped
+                .into_iter()
+                .filter_map(|test_case| match test_case {
+                    TestCase::Skip { name, reason } => Some(Skipped { name, reason }),
+                    TestCase::Test { .. } => None,
+                })
+                .collect(),
         }
     }
+
+    /// Write the test-suite to a YAML file, overwriting it if it already exists
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let yml = BufWriter::new(File::create(path)?);
+
+        Ok(serde_yaml::to_writer(yml, self)?)
+    }
+
+    /// Read a test-suite back from a YAML file
+    pub fn read(path: &Path) -> Result<TestSuite, Error> {
+        let yml = fs::read_to_string(path)?;
+
+        Ok(serde_yaml::from_str(&yml)?)
+    }
 }
 
 pub trait Pass: Sync {
-    /// Fetch test cases
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error>;
+    /// Fetch test cases. Paths inside the rust repository are resolved through its
+    /// [`RustLayout`], which is probed once per invocation
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error>;
 
     /// Adapt test cases, running any kind of transformation on them and providing
-    /// extra information necessary for the test case generation
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error>;
+    /// extra information necessary for the test case generation. A single file can
+    /// result in multiple test cases, for example when it needs to be compiled in
+    /// multiple configurations. The [`Oracle`] is shared between all passes ran in
+    /// the same invocation
+    fn adapt(&self, args: &Args, oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error>;
+}
+
+/// Copy the `ui` test files of the rustc test-suite selected by the arguments to
+/// `output_dir`, as well as their expected outputs ending with `expected_output`, such as
+/// `.stderr`, if the pass needs them. Returns the paths of the copied test files
+pub fn fetch_ui_tests(
+    args: &Args,
+    layout: &RustLayout,
+    output_dir: &Path,
+    expected_output: Option<&str>,
+) -> Result<Vec<PathBuf>, Error> {
+    let rust_path = layout.root();
+    let ui_tests = layout.ui_tests();
+
+    let files = copy_test_files(args, &ui_tests, output_dir, rust_path)?;
+
+    if let Some(suffix) = expected_output {
+        copy_expected_outputs(&files, &ui_tests, output_dir, rust_path, suffix)?;
+    }
+
+    Ok(files)
+}
+
+/// Adapt a rustc test case once per revision, using the headers of each revision. Test
+/// cases without revisions are adapted once
+pub fn adapt_revisions(
+    content: &str,
+    adapt_revision: impl FnMut(Headers) -> Result<TestCase, Error>,
+) -> Result<Vec<TestCase>, Error> {
+    Headers::parse_revisions(content)
+        .into_iter()
+        .map(adapt_revision)
+        .collect()
 }
 
 /// Passes to run when generating the test-suite file. One can chose to run only
 /// a specific pass, or multiple of them
-#[derive(Clone, Copy, clap::ValueEnum)]
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum PassKind {
     /// Generates test cases for running gccrs and rustc in parse-only mode on
     /// the rustc test suite
@@ -166,20 +367,57 @@ pub enum PassKind {
     GccrsRustcSucessNoStd,
     /// Testsuite for running gccrs on valid rustc test cases in #![no_core] mode
     GccrsRustcSucessNoCore,
+    /// Testsuite checking that gccrs reports the errors annotated in invalid rustc test cases
+    GccrsRustcErrors,
+    /// Testsuite checking that binaries produced by gccrs from rustc test cases behave like
+    /// the ones produced by rustc
+    GccrsRustcRun,
+    /// Testsuite scoring how closely the errors reported by gccrs match the ones in the
+    /// `.stderr` files of the rustc test suite
+    GccrsRustcStderr,
     /// Compile the reference implementation of the Blake3 cryptographic algorithm
     Blake3,
     /// Compile the core library from various rust versions
     LibCore,
     /// Test our AST exporting algorithm on the whole gccrs testsuite
     AstExport,
+    /// Run all of the above passes
+    All,
 }
 
-#[derive(Debug)]
-pub struct InvalidPassKind(String);
+impl PassKind {
+    /// All the passes which can be run by the adaptor
+    pub fn variants() -> [PassKind; 11] {
+        [
+            PassKind::GccrsParsing,
+            PassKind::RustcDejagnu,
+            PassKind::GccrsRustcSucess,
+            PassKind::GccrsRustcSucessNoStd,
+            PassKind::GccrsRustcSucessNoCore,
+            PassKind::GccrsRustcErrors,
+            PassKind::GccrsRustcRun,
+            PassKind::GccrsRustcStderr,
+            PassKind::Blake3,
+            PassKind::LibCore,
+            PassKind::AstExport,
+        ]
+    }
 
-impl Display for InvalidPassKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid pass name provided: {}", self.0)
+    /// Expand a list of passes given on the command line, replacing `all` with every
+    /// available pass and removing duplicates while keeping the original order
+    pub fn expand(kinds: &[PassKind]) -> Vec<PassKind> {
+        kinds
+            .iter()
+            .flat_map(|kind| match kind {
+                PassKind::All => PassKind::variants().to_vec(),
+                kind => vec![*kind],
+            })
+            .fold(Vec::new(), |mut acc, kind| {
+                if !acc.contains(&kind) {
+                    acc.push(kind);
+                }
+                acc
+            })
     }
 }
 
@@ -191,9 +429,13 @@ impl Display for PassKind {
             PassKind::GccrsRustcSucess => "gccrs-rustc-success",
             PassKind::GccrsRustcSucessNoStd => "gccrs-rustc-success-no-std",
             PassKind::GccrsRustcSucessNoCore => "gccrs-rustc-success-no-core",
+            PassKind::GccrsRustcErrors => "gccrs-rustc-errors",
+            PassKind::GccrsRustcRun => "gccrs-rustc-run",
+            PassKind::GccrsRustcStderr => "gccrs-rustc-stderr",
             PassKind::Blake3 => "blake3",
             PassKind::LibCore => "libcore",
             PassKind::AstExport => "ast-export",
+            PassKind::All => "all",
         };
 
         write!(f, "{s}")
@@ -1,15 +1,29 @@
 use std::fs;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::path::{self, Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use wait_timeout::ChildExt;
+use rayon::prelude::*;
+use regex::Regex;
 
 use crate::args::Args;
 use crate::compiler::{Compiler, Kind};
 use crate::error::Error;
-use crate::fetch_rust_files;
+use crate::fetch_test_files;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
 use crate::passes::{Pass, TestCase};
+use crate::runner;
+
+/// Maximum amount of time given to the original binary of a test case to run
+const RUN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pattern matching an empty output
+const EMPTY_OUTPUT: &str = r"\A\z";
+
+/// Source location printed by a binary, such as `src/main.rs:12:5` in a panic message
+const LOCATION: &str = r"[^\s:'\x22]+\.(?:rs|pretty-rs):\d+(?::\d+)?";
 
 fn get_original_file_from_pretty(pretty_file: &Path) -> PathBuf {
     let mut original_file = pretty_file.to_owned().with_extension("rs");
@@ -40,6 +54,7 @@ fn adapt_compilation(args: &Args, pretty_file: &Path) -> Result<TestCase, Error>
 fn adapt_run(args: &Args, pretty_file: &Path) -> Result<TestCase, Error> {
     let original_file = get_original_file_from_pretty(pretty_file);
     let binary_name = original_file.with_extension("");
+    let name = format!("Run prettified binary from `{}`", original_file.display());
 
     // Build the original binary
     if !Compiler::new(Kind::Rust1, args)
@@ -50,112 +65,254 @@ fn adapt_run(args: &Args, pretty_file: &Path) -> Result<TestCase, Error> {
         .status()?
         .success()
     {
-        // This will be handled by the `AstExport::Compile` part
-        return Ok(TestCase::Skip);
+        // This will be handled by the `Phase::Compile` part
+        return Ok(TestCase::skip(name, "original file does not compile"));
     }
 
-    let mut child = Command::new(binary_name.as_os_str())
-        .stderr(Stdio::null())
-        .stdout(Stdio::null())
-        .spawn()?;
-
     // Run the original binary
-    let binary_exit_code = if let Some(exit_status) = child.wait_timeout(Duration::from_secs(5))? {
-        exit_status.code()
+    let output = runner::run_with_timeout(&mut Command::new(&binary_name), RUN_TIMEOUT)?;
+    if output.timed_out {
+        return Ok(TestCase::skip(name, "original binary timed out"));
+    }
+
+    let Some(code) = output.exit_code else {
+        return Ok(TestCase::skip(
+            name,
+            "original binary was killed by a signal",
+        ));
+    };
+
+    let binary_name = binary_name.with_extension("pretty");
+    // We now build the "prettified binary". If that fails, skip it as that's been handled by the `Compile` phase
+    if !Compiler::new(Kind::Rust1, args)
+        .command()
+        .arg(pretty_file)
+        .arg("-o")
+        .arg(binary_name.as_os_str())
+        .status()?
+        .success()
+    {
+        return Ok(TestCase::skip(name, "prettified file does not compile"));
+    }
+
+    // The prettified binary must print exactly what the original one printed. An empty
+    // expected output is not checked, so it is expressed as a pattern instead
+    let test_case = TestCase::default()
+        .with_name(name)
+        .with_binary(binary_name.display())
+        .with_exit_code(u8::try_from(code)?)
+        .with_stdout(&output.stdout);
+    let test_case = if output.stdout.is_empty() {
+        test_case.with_stdout_pattern(EMPTY_OUTPUT)
     } else {
-        child.kill()?;
-        return Ok(TestCase::Skip);
+        test_case
     };
 
-    match binary_exit_code {
-        None => Ok(TestCase::Skip),
-        Some(code) => {
-            let binary_name = binary_name.with_extension("pretty");
-            // We now build the "prettified binary". If that fails, skip it as that's been handled by the `Compile` phase
-            if !Compiler::new(Kind::Rust1, args)
-                .command()
-                .arg(pretty_file)
-                .arg("-o")
-                .arg(binary_name.as_os_str())
-                .status()?
-                .success()
-            {
-                return Ok(TestCase::Skip);
-            }
+    Ok(if args.compare_stderr {
+        test_case.with_stderr_pattern(stderr_pattern(&output.stderr))
+    } else {
+        test_case
+    })
+}
 
-            // TODO: Should we also check that the output is the same?
-            // TODO: Maybe just for stdout but not stderr as we do not guarantee the same exact output? So location info might be different
-            let test_case = TestCase::default()
-                .with_name(format!(
-                    "Run prettified binary from `{}`",
-                    original_file.display()
-                ))
-                .with_binary(binary_name.display())
-                .with_exit_code(u8::try_from(code)?);
-
-            Ok(test_case)
-        }
+/// Pattern matching the standard error of the original binary, where source locations can
+/// differ since they point into the prettified file
+fn stderr_pattern(stderr: &str) -> String {
+    let location = Regex::new(LOCATION).expect("invalid location regex");
+    let mut pattern = String::from(r"\A");
+    let mut last = 0;
+
+    for location in location.find_iter(stderr) {
+        pattern.push_str(&regex::escape(&stderr[last..location.start()]));
+        pattern.push_str(LOCATION);
+        last = location.end();
     }
+
+    pattern.push_str(&regex::escape(&stderr[last..]));
+    pattern.push_str(r"\z");
+
+    pattern
 }
 
-pub enum AstExport {
+/// Name of the file `gccrs` writes its prettified AST to, in its working directory
+const AST_PRETTY_DUMP: &str = "gccrs.ast-pretty.dump";
+
+/// Phase of the [`AstExport`] pass
+pub enum Phase {
     Compile,
     Run,
+    /// Prettifying the prettified file gives the same file
+    Fixpoint,
+}
+
+/// Pass checking that the AST prettified by `gccrs` is valid Rust code, which behaves like
+/// the original test case and is stable when prettified again. Its phases share the
+/// prettified files, which are only produced once
+pub struct AstExport {
+    phase: Phase,
+    prettified: Arc<Mutex<Option<Vec<PathBuf>>>>,
+}
+
+impl AstExport {
+    /// All phases of the [`AstExport`] pass, sharing their prettified files
+    pub fn phases() -> Vec<AstExport> {
+        let prettified = Arc::new(Mutex::new(None));
+
+        [Phase::Compile, Phase::Run, Phase::Fixpoint]
+            .into_iter()
+            .map(|phase| AstExport {
+                phase,
+                prettified: Arc::clone(&prettified),
+            })
+            .collect()
+    }
+}
+
+/// Prettify the AST of `file` into `output`. `gccrs` always writes the dump to its working
+/// directory, so each invocation runs in its own scratch directory. Returns whether `gccrs`
+/// managed to produce a dump
+fn dump_ast(args: &Args, file: &Path, output: &Path) -> Result<bool, Error> {
+    let mut scratch_dir = output.as_os_str().to_owned();
+    scratch_dir.push(".dump");
+    let scratch_dir = PathBuf::from(scratch_dir);
+
+    fs::create_dir_all(&scratch_dir)?;
+
+    Compiler::new(Kind::Rust1, args)
+        .current_dir(&scratch_dir)
+        .command()
+        .arg(path::absolute(file)?)
+        .arg("-frust-dump-ast-pretty")
+        // No need to go further in the pipeline
+        .arg("-frust-compile-until=lowering")
+        .status()?;
+
+    // Files which `gccrs` fails to parse do not have an AST to prettify
+    let dump = scratch_dir.join(AST_PRETTY_DUMP);
+    let dumped = dump.exists();
+    if dumped {
+        fs::rename(dump, output)?;
+    }
+
+    fs::remove_dir_all(&scratch_dir)?;
+
+    Ok(dumped)
+}
+
+/// Copy a test case to `output_dir`, while stripping the prefix `prefix_to_strip` from its
+/// path, and prettify its AST next to the copy. Returns the path of the prettified file, if
+/// `gccrs` managed to produce one
+fn prettify(
+    args: &Args,
+    file: &Path,
+    output_dir: &Path,
+    prefix_to_strip: &Path,
+) -> Result<Option<PathBuf>, Error> {
+    let new_path_original = output_dir.join(file.strip_prefix(prefix_to_strip)?);
+    let new_path = new_path_original.with_extension("pretty-rs");
+
+    if let Some(parent) = new_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::copy(file, &new_path_original)?;
+
+    Ok(dump_ast(args, file, &new_path)?.then_some(new_path))
+}
+
+/// First line on which two dumps differ, with its content in each dump. A dump ending
+/// early is reported as an empty line
+fn first_divergence<'a>(first: &'a str, second: &'a str) -> Option<(usize, &'a str, &'a str)> {
+    let mut first_lines = first.lines();
+    let mut second_lines = second.lines();
+
+    for line in 1.. {
+        match (first_lines.next(), second_lines.next()) {
+            (None, None) => return None,
+            (first, second) if first == second => {}
+            (first, second) => {
+                return Some((line, first.unwrap_or_default(), second.unwrap_or_default()))
+            }
+        }
+    }
+
+    None
+}
+
+/// Prettify the prettified file again: a correct pretty-printer reaches a fixpoint, and
+/// produces the exact same dump. The dumps are compared right away, and the test case
+/// checks that the second dump can still be parsed. If the dumps differ, the test case
+/// fails with the first divergent line
+fn adapt_fixpoint(args: &Args, pretty_file: &Path) -> Result<TestCase, Error> {
+    let original_file = get_original_file_from_pretty(pretty_file);
+    let name = format!("Prettify again `{}`", original_file.display());
+    let fixpoint_file = pretty_file.with_extension("fixpoint-rs");
+
+    // This will be handled by the `Phase::Compile` part
+    if !dump_ast(args, pretty_file, &fixpoint_file)? {
+        return Ok(TestCase::skip(name, "prettified file cannot be parsed"));
+    }
+
+    let first = fs::read_to_string(pretty_file)?;
+    let second = fs::read_to_string(&fixpoint_file)?;
+
+    let test_case = TestCase::from_compiler(Compiler::new(Kind::Rust1, args).parse_only())
+        .with_name(name)
+        .with_arg(fixpoint_file.display())
+        .with_exit_code(0);
+
+    Ok(match first_divergence(&first, &second) {
+        Some((line, expected, got)) => test_case.with_failure(format!(
+            "line {}: `{}` became `{}`",
+            line,
+            expected.trim(),
+            got.trim()
+        )),
+        None => test_case,
+    })
 }
 
 impl Pass for AstExport {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
+    fn fetch(&self, args: &Args, _layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        let mut prettified = self
+            .prettified
+            .lock()
+            .expect("prettified files lock poisoned");
+
+        // The files are prettified by the first phase, and reused by the following ones
+        if let Some(files) = prettified.as_ref() {
+            return Ok(files.clone());
+        }
+
         let gccrs_path = &args.gccrs_path;
         let tests_path = gccrs_path.join("gcc").join("testsuite").join("rust");
         let output_dir = args.output_dir.join("ast-export");
 
-        // Figure out a nice way to cache things since we don't need to do the copy twice
-        // if let AstExport::Run = self {
-        //      // The copies are already created in the `Compile` phase
-        //      return Ok(fetch_rust_files(&output_dir)
-        //          .into_iter()
-        //          .map(|entry| entry.path().to_owned())
-        //          .collect());
-        //  }
-
-        // For each file:
-        //      gccrs -frust-dump-ast-pretty <file>
-        //      cp gccrs.ast-pretty.dump <new_path>
-        let new_files = fetch_rust_files(&tests_path)
-            // FIXME: Cannot parallelize this since the AST dump is always the same file...
-            // Think about -frust-dump-ast-pretty=<file>?
+        let new_files = fetch_test_files(args, &tests_path, gccrs_path)
+            .into_par_iter()
+            .map(|entry| prettify(args, entry.path(), &output_dir, gccrs_path))
+            .collect::<Result<Vec<_>, Error>>()?
             .into_iter()
-            .map(|entry| {
-                let new_path_original = output_dir.join(entry.path());
-                let new_path = output_dir.join(entry.path()).with_extension("pretty-rs");
-
-                Compiler::new(Kind::Rust1, args)
-                    .command()
-                    .arg(entry.path())
-                    .arg("-frust-dump-ast-pretty")
-                    // No need to go further in the pipeline
-                    .arg("-frust-compile-until=lowering")
-                    .status()?;
-
-                // Make sure the directory exists
-                if let Some(parent) = new_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-
-                fs::copy(entry.path(), new_path_original)?;
-                fs::copy("gccrs.ast-pretty.dump", &new_path)?;
-
-                Ok(new_path)
-            })
-            .collect::<Result<Vec<PathBuf>, Error>>()?;
+            .flatten()
+            .collect::<Vec<PathBuf>>();
+
+        *prettified = Some(new_files.clone());
 
         Ok(new_files)
     }
 
-    fn adapt(&self, args: &Args, pretty_file: &Path) -> Result<TestCase, Error> {
-        match self {
-            AstExport::Compile => adapt_compilation(args, pretty_file),
-            AstExport::Run => adapt_run(args, pretty_file),
-        }
+    fn adapt(
+        &self,
+        args: &Args,
+        _oracle: &Oracle,
+        pretty_file: &Path,
+    ) -> Result<Vec<TestCase>, Error> {
+        let test_case = match self.phase {
+            Phase::Compile => adapt_compilation(args, pretty_file),
+            Phase::Run => adapt_run(args, pretty_file),
+            Phase::Fixpoint => adapt_fixpoint(args, pretty_file),
+        }?;
+
+        Ok(vec![test_case])
     }
 }
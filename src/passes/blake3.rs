@@ -4,6 +4,8 @@ use std::path::{Path, PathBuf};
 use crate::args::Args;
 use crate::compiler::{Compiler, CrateType, Kind};
 use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
 use crate::passes::{Pass, TestCase};
 
 /// Taken directly from [the Blake3 Rust reference implementation](https://github.com/BLAKE3-team/BLAKE3/blob/master/reference_impl/reference_impl.rs)
@@ -43,7 +45,7 @@ impl Blake3 {
 }
 
 impl Pass for Blake3 {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
+    fn fetch(&self, args: &Args, _layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
         let output_file = args
             .output_dir
             .clone()
@@ -56,7 +58,7 @@ impl Pass for Blake3 {
         Ok(vec![output_file])
     }
 
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error> {
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
         let prelude = match self {
             Blake3::GccrsOriginal => "",
             Blake3::RustcNoStd => "#![no_std]\n",
@@ -73,12 +75,12 @@ impl Pass for Blake3 {
 
         fs::write(file, format!("{prelude}{BLAKE3_TEMPLATE}"))?;
 
-        Ok(TestCase::from_compiler(compiler)
+        Ok(vec![TestCase::from_compiler(compiler)
             .with_arg(file.display())
             .with_name(format!(
                 "Compile Blake3 reference implementation ({})",
                 self.suffix()
             ))
-            .with_exit_code(0))
+            .with_exit_code(0)])
     }
 }
@@ -1,40 +1,66 @@
 use crate::args::Args;
-use crate::compiler::{Compiler, Edition, Kind};
-use crate::copy_rs_files;
+use crate::compiler::{Compiler, Kind};
+use crate::compiletest::Headers;
+use crate::copy_test_files;
 use crate::error::Error;
-use crate::passes::{Pass, TestCase};
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
+use crate::passes::{adapt_revisions, Pass, TestCase};
 
+use std::fs;
 use std::path::{Path, PathBuf};
 
 pub struct GccrsParsing;
 
 impl Pass for GccrsParsing {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
-        let rust_path = &args.rust_path;
-        let ui_tests = rust_path.join("src").join("test");
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        let rust_path = layout.root();
+        let tests = layout.tests();
 
-        copy_rs_files(&ui_tests, &args.output_dir, rust_path)
+        copy_test_files(args, &tests, &args.output_dir, rust_path)
     }
 
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error> {
-        let is_valid = Compiler::new(Kind::RustcBootstrap, args)
-            .edition(Edition::E2021)
-            .command()
-            // FIXME: We need to instead build a specific version of rustc to test against rather than using the user's
-            // FIXME: We can maybe instead use the rustc-ap-rustc_parse crate which would be much faster
-            .arg("-Z")
-            .arg("parse-only")
-            .arg(file.as_os_str())
-            .status()?
-            .success();
-
-        let test_case = TestCase::from_compiler(Compiler::new(Kind::Rust1, args))
-            .with_name(format!("Parse `{}`", file.display()))
-            .with_exit_code(u8::from(!is_valid))
-            .with_timeout(1)
-            .with_arg("-fsyntax-only")
-            .with_arg(file.display());
-
-        Ok(test_case)
+    fn adapt(&self, args: &Args, oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
+        adapt_revisions(&String::from_utf8_lossy(&fs::read(file)?), |headers| {
+            adapt_revision(args, oracle, file, headers)
+        })
     }
 }
+
+/// Parse a single revision of a test case with both compilers
+fn adapt_revision(
+    args: &Args,
+    oracle: &Oracle,
+    file: &Path,
+    headers: Headers,
+) -> Result<TestCase, Error> {
+    let name = format!("Parse `{}`", headers.display(file));
+
+    if let Some(reason) = &headers.ignore {
+        return Ok(TestCase::skip(name, reason));
+    }
+
+    // FIXME: We need to instead build a specific version of rustc to test against rather than using the user's
+    // FIXME: We can maybe instead use the rustc-ap-rustc_parse crate which would be much faster
+    let is_valid = oracle.accepts(
+        headers
+            .maybe_cfg(Compiler::new(Kind::RustcBootstrap, args))
+            .maybe_edition(headers.edition)
+            .flags(&headers.compile_flags)
+            .parse_only(),
+        file,
+    )?;
+
+    let test_case = TestCase::from_compiler(
+        headers
+            .maybe_cfg(Compiler::new(Kind::Rust1, args))
+            .maybe_edition(headers.edition),
+    )
+    .with_name(name)
+    .with_exit_code(u8::from(!is_valid))
+    .with_timeout(1)
+    .with_arg("-fsyntax-only")
+    .with_arg(file.display());
+
+    Ok(test_case)
+}
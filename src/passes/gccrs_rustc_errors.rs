@@ -0,0 +1,73 @@
+use crate::args::Args;
+use crate::auxiliary::AuxCrates;
+use crate::compiletest::Headers;
+use crate::diagnostics::ExpectedErrors;
+use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
+use crate::passes::{adapt_revisions, fetch_ui_tests, Pass, TestCase};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Check that gccrs reports the errors expected by the `//~ ERROR` annotations of invalid
+/// rustc test cases, and only those
+pub struct GccrsRustcErrors;
+
+impl Pass for GccrsRustcErrors {
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        fetch_ui_tests(args, layout, &args.output_dir, None)
+    }
+
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
+        adapt_revisions(&String::from_utf8_lossy(&fs::read(file)?), |headers| {
+            adapt_revision(args, file, headers)
+        })
+    }
+}
+
+/// Check the errors of a single revision of a test case against its annotations
+fn adapt_revision(args: &Args, file: &Path, headers: Headers) -> Result<TestCase, Error> {
+    let name = format!("Match errors `{}`", headers.display(file));
+
+    if let Some(reason) = &headers.ignore {
+        return Ok(TestCase::skip(name, reason));
+    }
+
+    // Test cases which compile, including the ones failing at runtime, have no errors to match
+    if headers.expects_success() {
+        return Ok(TestCase::skip(name, "test case is expected to compile"));
+    }
+
+    // `error-pattern` messages are specific to rustc, and do not tell where errors are
+    if headers.annotations.is_empty() && !headers.error_patterns.is_empty() {
+        return Ok(TestCase::skip(
+            name,
+            "test case only checks its errors with `error-pattern`",
+        ));
+    }
+
+    if headers.annotations.is_empty() {
+        return Ok(TestCase::skip(
+            name,
+            "test case does not have error annotations",
+        ));
+    }
+
+    let gccrs = AuxCrates::resolve(file, &headers).gccrs(args)?;
+    let file = file.display().to_string();
+
+    let test_case =
+        TestCase::from_compiler(headers.maybe_cfg(gccrs).maybe_edition(headers.edition))
+            .with_name(name)
+            .with_exit_code(1)
+            // FIXME: Use proper duration here (#10)
+            .with_timeout(5 * 60)
+            .with_arg(&file)
+            .with_expected_errors(ExpectedErrors {
+                file,
+                annotations: headers.annotations,
+            });
+
+    Ok(test_case)
+}
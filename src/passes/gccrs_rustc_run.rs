@@ -0,0 +1,158 @@
+use crate::args::Args;
+use crate::auxiliary::AuxCrates;
+use crate::compiler::{Compiler, Kind};
+use crate::compiletest::{Headers, Mode};
+use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
+use crate::passes::{adapt_revisions, fetch_ui_tests, Pass, TestCase};
+use crate::runner;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Maximum amount of time given to a compiler to build a test case
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum amount of time given to a test case's binary to run, in seconds
+const RUN_TIMEOUT: u64 = 10;
+
+/// Exit code of Rust programs which panicked
+const PANIC_EXIT_CODE: u8 = 101;
+
+/// Build the `run-pass` and `run-fail` rustc test cases with both rustc and gccrs, and
+/// check that the binary produced by gccrs behaves like the one produced by rustc
+pub struct GccrsRustcRun;
+
+impl Pass for GccrsRustcRun {
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        fetch_ui_tests(args, layout, &args.output_dir, Some(".run.stdout"))
+    }
+
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
+        adapt_revisions(&String::from_utf8_lossy(&fs::read(file)?), |headers| {
+            adapt_revision(args, file, headers)
+        })
+    }
+}
+
+/// Path of the binary built from a test case by a specific compiler, which depends on the
+/// revision so that all revisions can be run
+fn binary_path(file: &Path, headers: &Headers, kind: Kind) -> PathBuf {
+    let compiler = match kind {
+        Kind::Rust1 => "gccrs",
+        Kind::RustcBootstrap => "rustc",
+    };
+
+    match &headers.revision {
+        Some(revision) => file.with_extension(format!("{revision}.{compiler}")),
+        None => file.with_extension(compiler),
+    }
+}
+
+/// Build a test case with a fully configured compiler
+fn build(mut compiler: Compiler, file: &Path) -> bool {
+    runner::run_with_timeout(compiler.command().arg(file), COMPILE_TIMEOUT)
+        .is_ok_and(|output| output.exit_code == Some(0))
+}
+
+/// Build a single revision of a test case with both compilers, and run the `gccrs` binary
+/// if the `rustc` one behaves as expected
+fn adapt_revision(args: &Args, file: &Path, headers: Headers) -> Result<TestCase, Error> {
+    let name = format!("Run `{}`", headers.display(file));
+
+    if let Some(reason) = &headers.ignore {
+        return Ok(TestCase::skip(name, reason));
+    }
+
+    let expected_exit_code = match headers.mode {
+        Some(Mode::RunPass) => 0,
+        Some(Mode::RunFail) => PANIC_EXIT_CODE,
+        _ => return Ok(TestCase::skip(name, "test case is not executed")),
+    };
+
+    let aux_crates = AuxCrates::resolve(file, &headers);
+
+    if let Some(aux) = aux_crates.build(Kind::RustcBootstrap, args)? {
+        return Ok(TestCase::skip(
+            name,
+            format!("auxiliary crate `{}` is rejected by rustc", aux.name),
+        ));
+    }
+
+    let rustc_binary = binary_path(file, &headers, Kind::RustcBootstrap);
+    let rustc = aux_crates.link(
+        Compiler::new(Kind::RustcBootstrap, args),
+        Kind::RustcBootstrap,
+    );
+
+    if !build(
+        headers
+            .maybe_cfg(rustc)
+            .maybe_edition(headers.edition)
+            .flags(&headers.compile_flags)
+            .output(&rustc_binary),
+        file,
+    ) {
+        return Ok(TestCase::skip(name, "test case is rejected by rustc"));
+    }
+
+    // Make sure the test case behaves as expected with rustc before comparing gccrs with it
+    let reference = runner::run_with_timeout(
+        &mut Command::new(&rustc_binary),
+        Duration::from_secs(RUN_TIMEOUT),
+    )?;
+
+    if reference.timed_out {
+        return Ok(TestCase::skip(name, "rustc binary timed out"));
+    }
+    if reference.exit_code != Some(i32::from(expected_exit_code)) {
+        return Ok(TestCase::skip(
+            name,
+            format!("rustc binary does not exit with code {expected_exit_code}"),
+        ));
+    }
+
+    let expected_stdout = headers.expected_stdout(file)?;
+    if expected_stdout
+        .as_ref()
+        .is_some_and(|stdout| *stdout != reference.stdout)
+    {
+        return Ok(TestCase::skip(
+            name,
+            "rustc binary does not print the expected output",
+        ));
+    }
+
+    let gccrs_binary = binary_path(file, &headers, Kind::Rust1);
+    let configure = |gccrs: Compiler| {
+        headers
+            .maybe_cfg(gccrs)
+            .maybe_edition(headers.edition)
+            .output(&gccrs_binary)
+    };
+
+    // The test case keeps its name when `gccrs` fails to build it, so that this shows up
+    // as a regression rather than as a skipped test case. The auxiliary crates are only
+    // built once
+    if !build(configure(aux_crates.gccrs(args)?), file) {
+        let gccrs = aux_crates.link(Compiler::new(Kind::Rust1, args), Kind::Rust1);
+
+        return Ok(TestCase::from_compiler(configure(gccrs))
+            .with_name(name)
+            .with_arg(file.display())
+            .with_exit_code(0)
+            .with_timeout(COMPILE_TIMEOUT.as_secs() as i32));
+    }
+
+    let test_case = TestCase::default()
+        .with_name(name)
+        .with_binary(gccrs_binary.display())
+        .with_exit_code(expected_exit_code)
+        .with_timeout(RUN_TIMEOUT as i32)
+        .with_stdout(expected_stdout.unwrap_or_default());
+
+    Ok(test_case)
+}
@@ -0,0 +1,84 @@
+use crate::args::Args;
+use crate::auxiliary::AuxCrates;
+use crate::compiletest::Headers;
+use crate::diagnostics::{self, ReferenceErrors};
+use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
+use crate::passes::{adapt_revisions, fetch_ui_tests, Pass, TestCase};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Compare the errors reported by gccrs with the ones reported by rustc, as found in the
+/// `.stderr` golden files of the rustc test-suite, and score how closely they match
+pub struct GccrsRustcStderr;
+
+impl Pass for GccrsRustcStderr {
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        fetch_ui_tests(args, layout, &args.output_dir, Some(".stderr"))
+    }
+
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
+        adapt_revisions(&String::from_utf8_lossy(&fs::read(file)?), |headers| {
+            adapt_revision(args, file, headers)
+        })
+    }
+}
+
+/// Golden file of a test case, `<test>.<revision>.stderr` or `<test>.stderr`
+fn stderr_file(file: &Path, headers: &Headers) -> Option<PathBuf> {
+    let revision_file = headers
+        .revision
+        .as_ref()
+        .map(|revision| file.with_extension(format!("{revision}.stderr")));
+
+    revision_file
+        .into_iter()
+        .chain(Some(file.with_extension("stderr")))
+        .find(|path| path.exists())
+}
+
+/// Score the errors of a single revision of a test case against its `.stderr` file
+fn adapt_revision(args: &Args, file: &Path, headers: Headers) -> Result<TestCase, Error> {
+    let name = format!("Compare errors `{}`", headers.display(file));
+
+    if let Some(reason) = &headers.ignore {
+        return Ok(TestCase::skip(name, reason));
+    }
+
+    if headers.expects_success() {
+        return Ok(TestCase::skip(name, "test case is expected to compile"));
+    }
+
+    let Some(stderr_file) = stderr_file(file, &headers) else {
+        return Ok(TestCase::skip(
+            name,
+            "test case does not have a `.stderr` file",
+        ));
+    };
+
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    let errors = diagnostics::rustc_errors(&fs::read_to_string(stderr_file)?, &file_name);
+
+    if errors.is_empty() {
+        return Ok(TestCase::skip(
+            name,
+            "`.stderr` file does not contain errors located in the test case",
+        ));
+    }
+
+    let gccrs = AuxCrates::resolve(file, &headers).gccrs(args)?;
+    let file = file.display().to_string();
+
+    let test_case =
+        TestCase::from_compiler(headers.maybe_cfg(gccrs).maybe_edition(headers.edition))
+            .with_name(name)
+            .with_exit_code(1)
+            // FIXME: Use proper duration here (#10)
+            .with_timeout(5 * 60)
+            .with_arg(&file)
+            .with_reference_errors(ReferenceErrors { file, errors });
+
+    Ok(test_case)
+}
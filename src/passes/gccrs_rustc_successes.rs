@@ -1,15 +1,15 @@
 use crate::args::Args;
-use crate::compiler::{Compiler, Edition, Kind};
-use crate::copy_rs_files;
+use crate::auxiliary::AuxCrates;
+use crate::compiler::{Compiler, CrateType, Kind};
+use crate::compiletest::Headers;
 use crate::error::Error;
-use crate::passes::{Pass, TestCase};
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
+use crate::passes::{adapt_revisions, fetch_ui_tests, Pass, TestCase};
 
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-
-use wait_timeout::ChildExt;
 
 pub enum GccrsRustcSuccesses {
     Full,
@@ -30,11 +30,8 @@ impl Display for GccrsRustcSuccesses {
 }
 
 impl Pass for GccrsRustcSuccesses {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
-        // FIXME: Do we want more tests than just src/test/ui?
-        let rust_path = &args.rust_path;
-        let ui_tests = rust_path.join("src").join("test").join("ui");
-
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
+        // FIXME: Do we want more tests than just the ui tests?
         let out_dir = match self {
             GccrsRustcSuccesses::Full => args.output_dir.clone(),
             // We need different output files since we're going to modify files for these
@@ -42,20 +39,12 @@ impl Pass for GccrsRustcSuccesses {
             GccrsRustcSuccesses::NoCore => args.output_dir.join("no-core"),
         };
 
-        copy_rs_files(&ui_tests, &out_dir, rust_path)
+        fetch_ui_tests(args, layout, &out_dir, None)
     }
 
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error> {
+    fn adapt(&self, args: &Args, oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
         let test_content = fs::read_to_string(file)?;
 
-        // FIXME: We should also see if the file contains a main function maybe?
-        // To know if we can pass `--crate-type=lib`?
-
-        // We're only interested in successes
-        if test_content.contains("ERROR") {
-            return Ok(TestCase::Skip);
-        }
-
         let extra_str = match self {
             GccrsRustcSuccesses::Full => "",
             GccrsRustcSuccesses::NoStd => "#![no_std]\n",
@@ -64,34 +53,77 @@ impl Pass for GccrsRustcSuccesses {
 
         fs::write(file, format!("{extra_str}{test_content}"))?;
 
+        adapt_revisions(&test_content, |headers| {
+            self.adapt_revision(args, oracle, file, headers)
+        })
+    }
+}
+
+impl GccrsRustcSuccesses {
+    /// Compile a single revision of a test case which is expected to succeed
+    fn adapt_revision(
+        &self,
+        args: &Args,
+        oracle: &Oracle,
+        file: &Path,
+        headers: Headers,
+    ) -> Result<TestCase, Error> {
+        let name = format!("Compile {} success `{}`", self, headers.display(file));
+
+        // FIXME: We should also see if the file contains a main function maybe?
+        // To know if we can pass `--crate-type=lib`?
+
+        if let Some(reason) = &headers.ignore {
+            return Ok(TestCase::skip(name, reason));
+        }
+
+        // We're only interested in successes
+        if !headers.expects_success() {
+            return Ok(TestCase::skip(name, "test case is expected to fail"));
+        }
+
+        let aux_crates = AuxCrates::resolve(file, &headers);
+
         if let GccrsRustcSuccesses::NoStd | GccrsRustcSuccesses::NoCore = self {
-            let mut child = Compiler::new(Kind::RustcBootstrap, args)
-                .edition(Edition::E2021)
-                .crate_name("rustc_output")
-                .command()
-                .arg("--crate-type")
-                .arg("lib")
-                .arg(file.as_os_str())
-                .spawn()?;
-
-            let is_valid = if let Some(status) = child.wait_timeout(Duration::from_secs(30))? {
-                status.success()
-            } else {
-                child.kill()?;
-                false
-            };
+            if let Some(aux) = aux_crates.build(Kind::RustcBootstrap, args)? {
+                return Ok(TestCase::skip(
+                    name,
+                    format!("auxiliary crate `{}` is rejected by rustc", aux.name),
+                ));
+            }
+
+            let rustc = aux_crates.link(
+                Compiler::new(Kind::RustcBootstrap, args),
+                Kind::RustcBootstrap,
+            );
+
+            let is_valid = oracle.accepts(
+                headers
+                    .maybe_cfg(rustc)
+                    .maybe_edition(headers.edition)
+                    .flags(&headers.compile_flags)
+                    .crate_name("rustc_output")
+                    .crate_type(CrateType::Library),
+                file,
+            )?;
 
             if !is_valid {
-                return Ok(TestCase::Skip);
+                return Ok(TestCase::skip(
+                    name,
+                    format!("test case is rejected by rustc in {self} mode"),
+                ));
             }
         }
 
-        let test_case = TestCase::from_compiler(Compiler::new(Kind::Rust1, args))
-            .with_name(format!("Compile {} success `{}`", self, file.display()))
-            .with_exit_code(0)
-            // FIXME: Use proper duration here (#10)
-            .with_timeout(5 * 60) // ftf's timeout is in seconds, so 5 minutes
-            .with_arg(file.display());
+        let gccrs = aux_crates.gccrs(args)?;
+
+        let test_case =
+            TestCase::from_compiler(headers.maybe_cfg(gccrs).maybe_edition(headers.edition))
+                .with_name(name)
+                .with_exit_code(0)
+                // FIXME: Use proper duration here (#10)
+                .with_timeout(5 * 60) // ftf's timeout is in seconds, so 5 minutes
+                .with_arg(file.display());
 
         Ok(test_case)
     }
@@ -1,4 +1,4 @@
-use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -6,6 +6,8 @@ use crate::args::Args;
 use crate::compiler::{Compiler, Kind};
 use crate::copy_rs_files;
 use crate::error::{Error, MiscKind};
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
 use crate::passes::{Pass, TestCase};
 use crate::steps::CompileStep;
 
@@ -30,10 +32,7 @@ impl LibCore {
 }
 
 impl Pass for LibCore {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
-        let rust_path = &args.rust_path;
-        let core_path = rust_path.join("library").join("core");
-
+    fn fetch(&self, args: &Args, layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
         let map_checkout = |success, arg_string| {
             if success {
                 Ok(())
@@ -43,39 +42,59 @@ impl Pass for LibCore {
         };
 
         let rust_git = |args: Vec<&str>| {
-            let old_dir = env::current_dir()?;
-            env::set_current_dir(rust_path)?;
-
-            let res = Command::new("git").args(&args).status()?;
-
-            env::set_current_dir(old_dir)?;
+            let res = Command::new("git")
+                .args(&args)
+                .current_dir(layout.root())
+                .status()?;
 
             map_checkout(res.success(), args.join(" "))
         };
 
-        rust_git(vec!["checkout", self.tag()])?;
+        // The tag is checked out in its own worktree, so that the shared checkout of the
+        // rust repository used by the other passes is left untouched
+        let checkout = fs::canonicalize(&args.output_dir)?.join(format!(".rust-{}", self.tag()));
+        let checkout_arg = checkout.to_string_lossy();
+
+        if checkout.exists() {
+            fs::remove_dir_all(&checkout)?;
+            rust_git(vec!["worktree", "prune"])?;
+        }
+
+        rust_git(vec![
+            "worktree",
+            "add",
+            "--quiet",
+            "--detach",
+            &checkout_arg,
+            self.tag(),
+        ])?;
 
-        copy_rs_files(&core_path, &args.output_dir, rust_path)?;
+        // The layout of the repository depends on the checked out tag
+        let tag_layout = RustLayout::probe(&checkout);
+        let copied = copy_rs_files(&tag_layout.core(), &args.output_dir, &checkout);
 
-        rust_git(vec!["checkout", "master"])?;
+        rust_git(vec!["worktree", "remove", "--force", &checkout_arg])?;
+        copied?;
 
         // We only want to compile a single file, and the others as modules
+        let core_root = tag_layout.core_root();
         Ok(vec![args
             .output_dir
-            .join(core_path)
-            .join("src")
-            .join("lib.rs")])
+            .join(core_root.strip_prefix(&checkout)?)])
     }
 
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error> {
-        Ok(TestCase::from_compiler(Compiler::new(Kind::Rust1, args))
-            .with_name(format!(
-                "Compiling libcore {} ({} step)",
-                self.tag(),
-                self.step().compile_option()
-            ))
-            .with_arg(file.display())
-            .with_arg(self.step().compile_option())
-            .with_exit_code(0))
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
+        Ok(vec![TestCase::from_compiler(Compiler::new(
+            Kind::Rust1,
+            args,
+        ))
+        .with_name(format!(
+            "Compiling libcore {} ({} step)",
+            self.tag(),
+            self.step().compile_option()
+        ))
+        .with_arg(file.display())
+        .with_arg(self.step().compile_option())
+        .with_exit_code(0)])
     }
 }
@@ -1,55 +1,198 @@
 use crate::args::Args;
-use crate::compiler::{Compiler, Kind};
-use crate::copy_rs_files;
+use crate::auxiliary::AuxCrates;
+use crate::compiler::{Compiler, CrateType, Kind};
+use crate::copy_test_files;
+use crate::dejagnu::{Action, Spec};
 use crate::error::Error;
+use crate::layout::RustLayout;
+use crate::oracle::Oracle;
 use crate::passes::{Pass, TestCase};
+use crate::runner;
 
 use std::fs;
-use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use regex::Regex;
+
+/// Maximum amount of time given to rustc to build an `execute` test case
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum amount of time given to an `execute` test case's binary to run, in seconds
+const RUN_TIMEOUT: i32 = 10;
+
+/// `main` function returning an exit code, which gccrs accepts but rustc does not
+const I32_MAIN: &str = r"(?m)^(\s*(?:pub\s+)?)fn\s+main\s*\(\s*\)\s*->\s*i32\b";
 
 pub struct RustcDejagnu;
 
 impl Pass for RustcDejagnu {
-    fn fetch(&self, args: &Args) -> Result<Vec<PathBuf>, Error> {
+    fn fetch(&self, args: &Args, _layout: &RustLayout) -> Result<Vec<PathBuf>, Error> {
         let gccrs_path = &args.gccrs_path;
         let tests_path = gccrs_path.join("gcc").join("testsuite").join("rust");
 
-        copy_rs_files(&tests_path, &args.output_dir, gccrs_path)
+        copy_test_files(args, &tests_path, &args.output_dir, gccrs_path)
     }
 
-    fn adapt(&self, args: &Args, file: &Path) -> Result<TestCase, Error> {
+    fn adapt(&self, args: &Args, _oracle: &Oracle, file: &Path) -> Result<Vec<TestCase>, Error> {
         // we have invalid UTF-8 testcases, so we cannot just use `fs::read_to_string`
-        let mut test_file = fs::File::open(file)?;
-        let mut bytes = Vec::new();
-        test_file.read_to_end(&mut bytes)?;
-
-        let exit_code = match String::from_utf8(bytes) {
-            Ok(content) => {
-                let mut exit_code = u8::from(content.contains("dg-error"));
-
-                // FIXME: This should be removed once we have a proper main shim in gccrs
-                // This is to make sure that we can't ever get a "success" because a test
-                // contains a dg-error directive and a `fn main() -> i32` so rustc produces
-                // the correct exit code
-                if content.contains("fn main()") {
-                    exit_code = 255;
-                }
-
-                exit_code
-            }
+        let content = match String::from_utf8(fs::read(file)?) {
+            Ok(content) => content,
             // invalid UTF-8 in the file
-            Err(_) => 1, // Is that stable?
+            Err(_) => {
+                let aux_crates = AuxCrates::resolve_link(file, "");
+                return Ok(vec![compile(
+                    args,
+                    &aux_crates,
+                    file,
+                    file,
+                    &Spec::default(),
+                    1,
+                )]); // Is that stable?
+            }
         };
 
-        let test_case = TestCase::from_compiler(Compiler::new(Kind::RustcBootstrap, args))
-            .with_name(format!("Run rustc on `{}`", file.display()))
-            .with_exit_code(exit_code)
-            .with_timeout(5)
-            .with_arg(file.display())
-            .with_arg("-o") // Compile all files to the same executable name to avoid having to clean up 500 executables...
-            .with_arg("rustc_out");
+        let spec = Spec::parse(&content, Action::default_for(file));
+        if let Some(reason) = &spec.skip {
+            return Ok(vec![TestCase::skip(
+                format!("Run rustc on `{}`", file.display()),
+                reason,
+            )]);
+        }
+
+        // The crates of `link` test cases are built in order before the test case itself.
+        // A dependency rejected by rustc is reported as the failure of the test case
+        let aux_crates = AuxCrates::resolve_link(file, &content);
+        if let Some(aux) = aux_crates.build(Kind::RustcBootstrap, args)? {
+            let dependency = Spec {
+                action: Action::Compile,
+                ..Spec::default()
+            };
+
+            return Ok(vec![compile(
+                args,
+                &aux_crates,
+                file,
+                &aux.path,
+                &dependency,
+                0,
+            )]);
+        }
+
+        if spec.expects_errors() {
+            return Ok(vec![compile(args, &aux_crates, file, file, &spec, 1)]);
+        }
+
+        let test_case = match spec.action {
+            Action::Run => run(args, &aux_crates, file, &content, &spec)?,
+            Action::Link => compile(
+                args,
+                &aux_crates,
+                file,
+                &with_main_shim(file, &content)?,
+                &spec,
+                0,
+            ),
+            _ => compile(args, &aux_crates, file, file, &spec, 0),
+        };
+
+        Ok(vec![test_case])
+    }
+}
+
+/// Compiler configured for a test case, with access to the crates it depends on. Test
+/// cases which are only compiled do not need a `main` function, and are built as libraries
+fn rustc(args: &Args, aux_crates: &AuxCrates, spec: &Spec) -> Compiler {
+    let compiler = aux_crates
+        .link(
+            Compiler::new(Kind::RustcBootstrap, args),
+            Kind::RustcBootstrap,
+        )
+        .maybe_edition(spec.edition());
 
-        Ok(test_case)
+    if spec.action.links() {
+        compiler
+    } else {
+        compiler.crate_type(CrateType::Library)
     }
 }
+
+/// Test case checking that rustc compiles the test case `file` with the expected exit code.
+/// `source` is the file given to rustc, which differs from `file` for shimmed test cases
+fn compile(
+    args: &Args,
+    aux_crates: &AuxCrates,
+    file: &Path,
+    source: &Path,
+    spec: &Spec,
+    exit_code: u8,
+) -> TestCase {
+    TestCase::from_compiler(rustc(args, aux_crates, spec))
+        .with_name(format!("Run rustc on `{}`", file.display()))
+        .with_exit_code(exit_code)
+        .with_timeout(5)
+        .with_arg(source.display())
+        .with_arg("-o") // Compile all files to the same executable name to avoid having to clean up 500 executables...
+        .with_arg("rustc_out")
+}
+
+/// gccrs test cases usually declare `fn main() -> i32`, which rustc rejects. Write a copy
+/// of such test cases where `main` is renamed and called by a shim exiting with the code it
+/// returns, and return its path. Other test cases are used as-is
+fn with_main_shim(file: &Path, content: &str) -> Result<PathBuf, Error> {
+    let main = Regex::new(I32_MAIN).expect("invalid main regex");
+    if !main.is_match(content) {
+        return Ok(file.to_owned());
+    }
+
+    // rustc derives the crate name from the file name, which cannot contain dots
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let shimmed = file.with_file_name(format!("{stem}_rustc.rs"));
+    let content = main.replace(content, "${1}fn gccrs_main() -> i32");
+
+    fs::write(
+        &shimmed,
+        format!("{content}\nfn main() {{\n    std::process::exit(gccrs_main());\n}}\n"),
+    )?;
+
+    Ok(shimmed)
+}
+
+/// Build an `execute` test case with rustc into its own binary, and run it. Its output
+/// must match its `dg-output` directives. Test cases which rustc fails to build are still
+/// compiled when running the test-suite, so that the failure is reported. Both test cases
+/// have the same name, so that a test case which stops building shows up as a regression
+fn run(
+    args: &Args,
+    aux_crates: &AuxCrates,
+    file: &Path,
+    content: &str,
+    spec: &Spec,
+) -> Result<TestCase, Error> {
+    let source = with_main_shim(file, content)?;
+    let binary = file.with_extension("rustc");
+
+    let built = runner::run_with_timeout(
+        rustc(args, aux_crates, spec)
+            .output(&binary)
+            .command()
+            .arg(&source),
+        COMPILE_TIMEOUT,
+    )
+    .is_ok_and(|output| output.exit_code == Some(0));
+
+    if !built {
+        return Ok(compile(args, aux_crates, file, &source, spec, 0));
+    }
+
+    let test_case = TestCase::default()
+        .with_name(format!("Run rustc on `{}`", file.display()))
+        .with_binary(binary.display())
+        .with_exit_code(0)
+        .with_timeout(RUN_TIMEOUT);
+
+    Ok(match spec.output_pattern() {
+        Some(pattern) => test_case.with_stdout_pattern(pattern),
+        None => test_case,
+    })
+}
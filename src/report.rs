@@ -0,0 +1,77 @@
+//! Machine-readable JSON report of a test run, containing the result of each test case
+//! as well as information about the compilers which were tested.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::version;
+use crate::error::Error;
+use crate::runner::{Summary, TestResult};
+
+/// Versions of the compilers used during a test run, as reported by `--version`. A version
+/// is missing if the compiler could not be launched
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Versions {
+    pub rustc: Option<String>,
+    pub gccrs: Option<String>,
+}
+
+impl Versions {
+    /// Launch each compiler with `--version` and keep the first line of its output
+    pub fn probe(rustc: Option<&Path>, gccrs: Option<&Path>) -> Versions {
+        Versions {
+            rustc: rustc.and_then(version),
+            gccrs: gccrs.and_then(version),
+        }
+    }
+}
+
+/// Information identifying a test run, so that dashboards can keep track of the results
+/// of each pass over time. Each field is only part of the report if it was given
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Commit of gccrs which was tested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    #[serde(flatten)]
+    pub run: RunInfo,
+    pub versions: Versions,
+    /// Serialized as `results`, which is what the nightly dashboards read
+    #[serde(rename = "results")]
+    pub summary: Summary,
+    pub tests: Vec<TestResult>,
+}
+
+impl Report {
+    pub fn new(run: RunInfo, versions: Versions, tests: Vec<TestResult>) -> Report {
+        Report {
+            run,
+            versions,
+            summary: Summary::new(&tests),
+            tests,
+        }
+    }
+
+    /// Read a report back from a JSON file
+    pub fn read(path: &Path) -> Result<Report, Error> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Write the report to a JSON file, overwriting it if it already exists
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let json = BufWriter::new(File::create(path)?);
+
+        Ok(serde_json::to_writer_pretty(json, self)?)
+    }
+}
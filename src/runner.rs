@@ -0,0 +1,384 @@
+//! Built-in test runner, which executes the generated test cases directly instead of
+//! going through `ftf`. The runner uses the global rayon thread pool, so the amount of
+//! test cases running in parallel is controlled by the `--jobs` argument.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use wait_timeout::ChildExt;
+
+use crate::diagnostics::{Diagnostics, Score};
+use crate::error::Error;
+use crate::passes::{TestCase, TestSuite};
+use crate::warn;
+
+/// Final state of a test case after running it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    Timeout,
+    /// The test case was skipped when generating the test-suite, and never ran
+    Skip,
+    /// The test case is expected to fail, and failed or timed out
+    XFail,
+    /// The test case is expected to fail, but passed
+    XPass,
+}
+
+impl Outcome {
+    /// Did the test case behave as `gccrs` should?
+    pub fn is_success(self) -> bool {
+        matches!(self, Outcome::Pass | Outcome::XPass)
+    }
+
+    /// Did the test case behave differently from how `gccrs` should, expectedly or not?
+    pub fn is_failure(self) -> bool {
+        matches!(self, Outcome::Fail | Outcome::Timeout | Outcome::XFail)
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let s = match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail => "fail",
+            Outcome::Timeout => "timeout",
+            Outcome::Skip => "skip",
+            Outcome::XFail => "xfail",
+            Outcome::XPass => "xpass",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+/// Result of running a single test case
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TestResult {
+    pub name: String,
+    /// Pass which generated the test case
+    pub pass: String,
+    /// Binary and arguments of the test case
+    pub command: Vec<String>,
+    pub expected_exit_code: u8,
+    /// Exit code of the process, if it exited normally before the timeout
+    pub exit_code: Option<i32>,
+    /// Time taken to run the test case, in seconds
+    pub duration: f64,
+    pub outcome: Outcome,
+    /// Reason for which the test case was skipped, is expected to fail, or failed when it
+    /// was already known to fail when generating it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Captured standard output of the test case, which is only kept in JUnit reports as
+    /// it can be much larger than the rest of the result
+    #[serde(skip)]
+    pub stdout: String,
+    /// Captured standard error of the test case
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub stderr: String,
+    /// Comparison of the reported errors with the expected ones, if the test case expects
+    /// specific errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<Diagnostics>,
+    /// Comparison of the reported errors with the ones reported by rustc, if the test case
+    /// has a reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+}
+
+impl TestResult {
+    /// Kind of the failure of a failed test case, and a message explaining it
+    pub fn failure(&self) -> (&'static str, String) {
+        // Unexpected failures only have a reason if it was found when generating them
+        if let (Outcome::Fail, Some(reason)) = (self.outcome, &self.reason) {
+            return ("output", reason.clone());
+        }
+
+        match (&self.diagnostics, &self.score) {
+            (Some(diagnostics), _) if !diagnostics.matches() => {
+                ("diagnostics", diagnostics.to_string())
+            }
+            (_, Some(score)) if !score.is_perfect() => ("diagnostics", score.to_string()),
+            _ if self.exit_code == Some(i32::from(self.expected_exit_code)) => {
+                ("output", String::from("unexpected output"))
+            }
+            _ => (
+                "exit-code",
+                format!(
+                    "expected exit code {}, got {}",
+                    self.expected_exit_code,
+                    self.exit_code
+                        .map_or_else(|| String::from("none"), |code| code.to_string())
+                ),
+            ),
+        }
+    }
+}
+
+/// Output of a process which ran to completion or was killed after its timeout
+pub struct Output {
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Read the entirety of a child's pipe in a separate thread, so that the child never
+/// blocks on a full pipe while we are waiting on it
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            // A failed read simply results in truncated output
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+}
+
+/// Spawn `cmd` and wait for it to complete, killing it if it takes longer than `timeout`
+pub fn run_with_timeout(cmd: &mut Command, timeout: Duration) -> Result<Output, Error> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let (exit_code, timed_out) = if let Some(status) = child.wait_timeout(timeout)? {
+        (status.code(), false)
+    } else {
+        child.kill()?;
+        child.wait()?;
+        (None, true)
+    };
+
+    Ok(Output {
+        exit_code,
+        timed_out,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Does an output match a regular expression? Like DejaGnu's `dg-output`, the expression
+/// can match any part of the output, and `.` also matches newlines. Invalid expressions
+/// never match
+fn matches_output(pattern: &str, output: &str) -> bool {
+    RegexBuilder::new(pattern)
+        .dot_matches_new_line(true)
+        .build()
+        .is_ok_and(|regex| regex.is_match(output))
+}
+
+/// Run a single test case, comparing its exit code and outputs with the expected ones.
+/// Expected outputs are only compared when they are not empty, similarly to `ftf`
+fn run_test(pass: &str, test_case: &TestCase) -> Option<TestResult> {
+    let TestCase::Test {
+        name,
+        binary,
+        exit_code: expected_exit_code,
+        timeout,
+        stderr,
+        stdout,
+        stdout_pattern,
+        stderr_pattern,
+        args,
+        xfail,
+        expected_errors,
+        reference_errors,
+        failure,
+    } = test_case
+    else {
+        return None;
+    };
+
+    let timeout = Duration::from_secs(u64::from(timeout.unsigned_abs()));
+    let start = Instant::now();
+
+    let mut result = TestResult {
+        name: name.clone(),
+        pass: pass.to_string(),
+        command: std::iter::once(binary).chain(args).cloned().collect(),
+        expected_exit_code: *expected_exit_code,
+        exit_code: None,
+        duration: 0.0,
+        outcome: Outcome::Fail,
+        reason: xfail.clone().or_else(|| failure.clone()),
+        stdout: String::new(),
+        stderr: String::new(),
+        diagnostics: None,
+        score: None,
+    };
+
+    match run_with_timeout(Command::new(binary).args(args), timeout) {
+        Ok(output) if output.timed_out => {
+            result.outcome = Outcome::Timeout;
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+        }
+        Ok(output) => {
+            result.exit_code = output.exit_code;
+            result.diagnostics = expected_errors
+                .as_ref()
+                .map(|expected| Diagnostics::new(expected, &output.stderr));
+            result.score = reference_errors
+                .as_ref()
+                .map(|reference| Score::new(reference, &output.stderr));
+
+            let is_success = output.exit_code == Some(i32::from(*expected_exit_code))
+                && (stdout.is_empty() || stdout == &output.stdout)
+                && stdout_pattern
+                    .as_ref()
+                    .is_none_or(|pattern| matches_output(pattern, &output.stdout))
+                && (stderr.is_empty() || stderr == &output.stderr)
+                && stderr_pattern
+                    .as_ref()
+                    .is_none_or(|pattern| matches_output(pattern, &output.stderr))
+                && result.diagnostics.as_ref().is_none_or(Diagnostics::matches)
+                && result.score.as_ref().is_none_or(Score::is_perfect)
+                && failure.is_none();
+
+            if is_success {
+                result.outcome = Outcome::Pass;
+            }
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+        }
+        // Failing to launch a test case is a failure of this test case only
+        Err(e) => {
+            warn!("could not launch `{}`: {}", name, e);
+            result.stderr = e.to_string();
+        }
+    }
+
+    result.duration = start.elapsed().as_secs_f64();
+    result.outcome = match (result.outcome, xfail) {
+        (Outcome::Pass, Some(_)) => Outcome::XPass,
+        (_, Some(_)) => Outcome::XFail,
+        (outcome, None) => outcome,
+    };
+
+    match result.outcome {
+        Outcome::Pass | Outcome::Skip | Outcome::XFail => {}
+        Outcome::XPass => warn!("`{}` is expected to fail, but passed (XPASS)", name),
+        Outcome::Fail => warn!("`{}` failed: {}", name, result.failure().1),
+        Outcome::Timeout => warn!("`{}` timed out after {:?}", name, timeout),
+    }
+
+    Some(result)
+}
+
+/// Run all the test cases of a test-suite in parallel. Skipped test cases are not ran,
+/// but still produce a result
+pub fn run(test_suite: &TestSuite) -> Vec<TestResult> {
+    let mut results: Vec<TestResult> = test_suite
+        .tests
+        .par_iter()
+        .filter_map(|test_case| run_test(&test_suite.pass, test_case))
+        .collect();
+
+    results.extend(test_suite.skipped.iter().map(|skipped| TestResult {
+        name: skipped.name.clone(),
+        pass: test_suite.pass.clone(),
+        command: vec![],
+        expected_exit_code: 0,
+        exit_code: None,
+        duration: 0.0,
+        outcome: Outcome::Skip,
+        reason: Some(skipped.reason.clone()),
+        stdout: String::new(),
+        stderr: String::new(),
+        diagnostics: None,
+        score: None,
+    }));
+
+    results
+}
+
+/// Amount of passing, failing and timed out tests in a test run. Timeouts are also counted
+/// as failures. Expected failures and unexpected passes are counted separately, and skipped
+/// tests are not part of the amount of tests ran
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub tests: usize,
+    pub passes: usize,
+    pub failures: usize,
+    pub timeouts: usize,
+    #[serde(default)]
+    pub skipped: usize,
+    #[serde(default)]
+    pub xfailures: usize,
+    #[serde(default)]
+    pub xpasses: usize,
+    /// Average diagnostics score of the test cases which have one. This is not part of
+    /// `ftf`'s format, and only appears in reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl Summary {
+    pub fn new(results: &[TestResult]) -> Summary {
+        let scores: Vec<f64> = results
+            .iter()
+            .filter_map(|result| result.score.as_ref())
+            .map(|score| score.score)
+            .collect();
+
+        let summary = results
+            .iter()
+            .fold(Summary::default(), |mut summary, result| {
+                if result.outcome != Outcome::Skip {
+                    summary.tests += 1;
+                }
+
+                match result.outcome {
+                    Outcome::Pass => summary.passes += 1,
+                    Outcome::Fail => summary.failures += 1,
+                    Outcome::Timeout => {
+                        summary.failures += 1;
+                        summary.timeouts += 1;
+                    }
+                    Outcome::Skip => summary.skipped += 1,
+                    Outcome::XFail => summary.xfailures += 1,
+                    Outcome::XPass => summary.xpasses += 1,
+                }
+
+                summary
+            });
+
+        Summary {
+            score: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+            ..summary
+        }
+    }
+}
+
+/// Use the same format as `ftf --result-fmt`, which is what the nightly workflow expects
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{{ \"tests\": {}, \"passes\": {}, \"failures\": {}, \"timeouts\": {}, \"skipped\": {}, \"xfailures\": {}, \"xpasses\": {} }}",
+            self.tests,
+            self.passes,
+            self.failures,
+            self.timeouts,
+            self.skipped,
+            self.xfailures,
+            self.xpasses
+        )
+    }
+}
@@ -0,0 +1,150 @@
+//! Lists of test cases which are known to fail in `gccrs`. Each pass reads its list from
+//! `<xfail-dir>/<pass>.xfail`, which contains one test name pattern per line, optionally
+//! followed by the reason for which the test case fails:
+//!
+//! ```text
+//! # Lines starting with a `#` are comments
+//! Parse `*/parser/issue-*.rs`
+//! Compile  success `*/ui/async-await/*` # async functions are not supported yet
+//! ```
+//!
+//! Patterns may contain any number of `*`, which match any sequence of characters.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::passes::{PassKind, TestCase};
+
+/// Separator between a pattern and the reason of the expected failure
+const REASON_SEPARATOR: &str = " # ";
+
+struct XFail {
+    pattern: String,
+    reason: String,
+}
+
+/// Check if `name` matches `pattern`, where `*` matches any sequence of characters
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // `split` always yields at least one element
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part needs to match the end of the name
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    // There was no `*` in the pattern
+    rest.is_empty()
+}
+
+#[derive(Default)]
+pub struct XFailList(Vec<XFail>);
+
+impl XFailList {
+    /// Load the list of expected failures of a pass. Passes without an xfail file have no
+    /// expected failures
+    pub fn load(dir: Option<&Path>, pass: PassKind) -> Result<XFailList, Error> {
+        match dir.map(|dir| dir.join(format!("{pass}.xfail"))) {
+            Some(path) if path.exists() => Ok(XFailList::parse(&fs::read_to_string(path)?)),
+            _ => Ok(XFailList::default()),
+        }
+    }
+
+    fn parse(content: &str) -> XFailList {
+        XFailList(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| {
+                    let (pattern, reason) = line
+                        .split_once(REASON_SEPARATOR)
+                        .unwrap_or((line, "no reason given"));
+
+                    XFail {
+                        pattern: pattern.trim().to_string(),
+                        reason: reason.trim().to_string(),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Mark a test case as an expected failure if its name matches one of the patterns
+    pub fn apply(&self, test_case: TestCase) -> TestCase {
+        let TestCase::Test { name, .. } = &test_case else {
+            return test_case;
+        };
+
+        match self.0.iter().find(|xfail| matches(&xfail.pattern, name)) {
+            Some(xfail) => test_case.with_xfail(&xfail.reason),
+            None => test_case,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_literal() {
+        assert!(matches("Parse `foo.rs`", "Parse `foo.rs`"));
+        assert!(!matches("Parse `foo.rs`", "Parse `foo.rs` again"));
+        assert!(!matches("Parse `foo.rs`", "Parse `bar.rs`"));
+        assert!(matches("", ""));
+        assert!(!matches("", "Parse `foo.rs`"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "Parse `foo.rs`"));
+        assert!(matches("Parse `*`", "Parse `ui/parser/foo.rs`"));
+        assert!(matches("*/parser/*", "Parse `ui/parser/foo.rs`"));
+        assert!(matches(
+            "Parse `*/parser/issue-*.rs`",
+            "Parse `ui/parser/issue-1234.rs`"
+        ));
+        assert!(!matches(
+            "Parse `*/parser/issue-*.rs`",
+            "Parse `ui/parser/foo.rs`"
+        ));
+        assert!(!matches("Parse `*`", "Compile `foo.rs`"));
+        // The part after the last `*` must match the end of the name
+        assert!(!matches("*.rs", "Parse `foo.rs`"));
+        assert!(matches("a*b*b", "abb"));
+        assert!(!matches("a*b*b", "ab"));
+    }
+
+    #[test]
+    fn parse_reasons() {
+        let list = XFailList::parse(
+            "# comment\n\n  Parse `*`  \nCompile  success `*/async/*` # not supported\n",
+        );
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.0[0].pattern, "Parse `*`");
+        assert_eq!(list.0[0].reason, "no reason given");
+        assert_eq!(list.0[1].pattern, "Compile  success `*/async/*`");
+        assert_eq!(list.0[1].reason, "not supported");
+    }
+}